@@ -0,0 +1,65 @@
+// Plays games with each pilot alone, without a terminal, and
+// reports how far it gets and how long it takes to decide on each move.
+//
+//   cargo bench --bench ai
+
+use std::time::Instant;
+
+use snake::ai::{ autoplay, Hamiltonian, Pathfinder, Pilot };
+use snake::engine::{ Engine, State };
+use snake::grid::Walls;
+use snake::level::Level;
+
+const GAMES: u64 = 10;
+// Games the pathfinder plays in circles are cut short, while the snake
+// following a cycle always gets to the end
+const MAX_TICKS: usize = 20_000;
+
+fn main() {
+    println!(
+        "{:>11}  {:>7}  {:>5}  {:>5}  {:>6}  {:>7}  {:>8}",
+        "pilot", "board", "walls", "won", "fill", "ticks", "us/tick",
+    );
+
+    for (pilot, max_ticks) in [("pathfinder", MAX_TICKS), ("hamiltonian", usize::MAX)] {
+        for (width, height) in [(10, 10), (20, 10), (36, 20)] {
+            for walls in [Walls::Solid, Walls::Open] {
+                let level = Level::empty(width, height);
+                let mut won = 0;
+                let mut cells = 0;
+                let mut ticks = 0;
+                let start = Instant::now();
+                for seed in 0..GAMES {
+                    let mut engine = Engine::new(&level, walls, seed);
+                    let mut pilots: Vec<Box<dyn Pilot>> = match pilot {
+                        "pathfinder" => vec![Box::new(Pathfinder::new())],
+                        _ => vec![Box::new(Hamiltonian::new())],
+                    };
+                    let result = autoplay(&mut engine, &mut pilots, max_ticks);
+                    if result.state == State::Won {
+                        won += 1;
+                    }
+                    cells += engine.snakes()[0].segments().count();
+                    ticks += result.ticks;
+                }
+                let elapsed = start.elapsed();
+
+                // How much of the board the snake took up in the end
+                let fill = cells as f64/(GAMES as usize*width*height) as f64;
+                println!(
+                    "{:>11}  {:>7}  {:>5}  {:>5}  {:>5.1}%  {:>7}  {:>8.1}",
+                    pilot,
+                    format!("{}x{}", width, height),
+                    match walls {
+                        Walls::Solid => "solid",
+                        Walls::Open => "open",
+                    },
+                    format!("{}/{}", won, GAMES),
+                    fill*100.0,
+                    ticks/GAMES as usize,
+                    elapsed.as_secs_f64()*1e6/ticks as f64,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// Compares the bytes written per frame when reprinting the whole board with
+// those written when printing only what changed since the previous frame.
+//
+//   cargo bench --bench render
+
+use rand::rngs::StdRng;
+use rand::{ Rng, SeedableRng };
+use termion::cursor;
+
+use snake::direction::Direction;
+use snake::engine::{ Engine, State };
+use snake::frame::{ DiffRenderer, Frame };
+use snake::grid::Walls;
+use snake::level::Level;
+
+const FRAMES: usize = 2000;
+const SEED: u64 = 1;
+// Each board is centred in a terminal this many columns and rows bigger.
+const SLACK: (usize, usize) = (20, 4);
+
+fn main() {
+    println!(
+        "{:>7}  {:>12}  {:>12}  {:>6}",
+        "board", "full B/frame", "diff B/frame", "ratio",
+    );
+
+    for (width, height) in [(20, 10), (36, 20), (80, 40), (160, 80)] {
+        let level = Level::empty(width, height);
+        let mut engine = Engine::new(&level, Walls::Open, SEED);
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let mut renderer = DiffRenderer::new();
+        let mut out = Vec::new();
+
+        let mut full_bytes = 0;
+        let mut diff_bytes = 0;
+        for _ in 0..FRAMES {
+            let inputs = if rng.gen_ratio(1, 5) {
+                let dirs = [
+                    Direction::Left,
+                    Direction::Down,
+                    Direction::Up,
+                    Direction::Right,
+                ];
+                vec![dirs[rng.gen_range(0..dirs.len())]]
+            } else {
+                vec![]
+            };
+            let (state, _) = engine.tick(&inputs);
+            if state != State::Playing {
+                engine = Engine::new(&level, Walls::Open, rng.gen());
+            }
+
+            let header = format!(" Score: {}", engine.points());
+            let grid = engine.grid().render();
+            let grid_width = grid.lines().next().map_or(0, |line| line.chars().count());
+            let grid_height = grid.lines().count();
+            let top_margin = ((grid_height + SLACK.1)/2 - grid_height/2) as u16;
+            let left_margin = (grid_width + SLACK.0)/2 - grid_width/2;
+
+            full_bytes += full_frame(top_margin, left_margin, engine.points(), &grid).len();
+
+            out.clear();
+            let frame = Frame::new([header.as_str()].into_iter().chain(grid.lines()));
+            renderer.draw(&mut out, frame, (left_margin as u16 + 1, top_margin)).unwrap();
+            diff_bytes += out.len();
+        }
+
+        println!(
+            "{:>7}  {:>12}  {:>12}  {:>5.1}x",
+            format!("{}x{}", width, height),
+            full_bytes/FRAMES,
+            diff_bytes/FRAMES,
+            full_bytes as f64/diff_bytes as f64,
+        );
+    }
+}
+
+// What drawing the frame took before the differential renderer, byte for
+// byte the output of the old `Game::render`.
+fn full_frame(top_margin: u16, left_margin: usize, points: usize, grid: &str) -> String {
+    let padding = String::from(" ").repeat(left_margin);
+    let output = grid
+        .lines()
+        .map(|line|
+            format!("{}{}", padding, line)
+        )
+        .collect::<Vec<_>>()
+        .join("\r\n");
+
+    format!("{}{} Score: {}\r\n{}", cursor::Goto(1, top_margin), padding, points, output)
+}
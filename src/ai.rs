@@ -0,0 +1,640 @@
+use std::collections::VecDeque;
+
+use crate::direction::{ Direction, are_opposite };
+use crate::engine::{ Engine, State };
+use crate::grid::{ Cell, Walls };
+use crate::position::Position;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Left,
+    Direction::Down,
+    Direction::Right,
+];
+
+// Never free, like walls and the bodies of dead snakes
+const TAKEN: usize = usize::MAX;
+
+// A computer player. Before each tick, it looks at the game and picks the
+// turn its snake takes, if any.
+pub trait Pilot {
+    fn steer(&mut self, engine: &Engine, player: usize) -> Option<Direction>;
+}
+
+// Takes the shortest path to the apple, but only when the snake could still
+// catch up with its own body once it got there, so that it never walls
+// itself in. Otherwise it follows its body, or at worst heads for the most
+// room.
+#[derive(Default)]
+pub struct Pathfinder {
+    // Ticks since the snake last grew, and its length then
+    hungry: usize,
+    len: usize,
+}
+
+impl Pathfinder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Pilot for Pathfinder {
+    fn steer(&mut self, engine: &Engine, player: usize) -> Option<Direction> {
+        let board = Board::new(engine);
+        let snake = &engine.snakes()[player];
+        let body = snake.segments().copied().collect::<Vec<_>>();
+        let head = body[0];
+        if body.len() == self.len {
+            self.hungry += 1;
+        } else {
+            self.hungry = 0;
+            self.len = body.len();
+        }
+
+        // Cells the other heads may move to next are only taken as a last
+        // resort, as running into each other kills both snakes
+        let mut contested = Vec::new();
+        for (i, other) in engine.snakes().iter().enumerate() {
+            if i != player && engine.is_alive(i) {
+                contested.extend(board.neighbors(other.head()).map(|(_, cell)| cell));
+            }
+        }
+        // Turning back is never possible, even for a snake without a neck
+        let moves = board.moves(head)
+            .into_iter()
+            .filter(|(dir, _)| !are_opposite(dir, &snake.dir()))
+            .collect::<Vec<_>>();
+        let uncontested = moves.iter()
+            .copied()
+            .filter(|(_, cell)| !contested.contains(cell))
+            .collect::<Vec<_>>();
+        let moves = if uncontested.is_empty() { moves } else { uncontested };
+
+        // Following the body can go round in circles, with the shortest path
+        // to the apple never safe. A snake that has not eaten in a long time
+        // tries the paths through its other moves as well.
+        let starving = self.hungry > board.free_from.len();
+        let apple = board.apple;
+        let mut paths = Vec::new();
+        if starving {
+            paths.extend(moves.iter()
+                .filter_map(|&first_move| board.path(head, apple?, &[first_move])));
+            paths.sort_by_key(Vec::len);
+        } else {
+            paths.extend(apple.and_then(|apple| board.path(head, apple, &moves)));
+        }
+        for path in paths {
+            let eaten = board.after_eating(&body, snake.is_growing(), &path);
+            let first_moves = eaten.board.moves(eaten.body[0]);
+            if eaten.board.is_safe(&eaten.body, &first_moves) {
+                return direction_to(&moves, path[0]);
+            }
+        }
+
+        // Among the safe moves, the one with the most room and the farthest
+        // from the apple buys the most time until a safe path to it opens up,
+        // unless the snake is starving
+        let mut best = None;
+        for &(dir, cell) in moves.iter() {
+            // Eating on the way holds up the tail, as on the path above
+            let (safe, room) = if Some(cell) == apple {
+                let eaten = board.after_eating(&body, snake.is_growing(), &[cell]);
+                let first_moves = eaten.board.moves(cell);
+                (
+                    eaten.board.is_safe(&eaten.body, &first_moves),
+                    eaten.board.room(cell, &first_moves),
+                )
+            } else {
+                (
+                    board.is_safe(&body, &[(dir, cell)]),
+                    board.room(head, &[(dir, cell)]),
+                )
+            };
+            let distance = apple.map_or(0, |apple| board.distance(cell, apple));
+            let score = if starving {
+                (safe, usize::MAX - distance, room)
+            } else {
+                (safe, room, distance)
+            };
+            if best.as_ref().is_none_or(|(best, _)| score > *best) {
+                best = Some((score, dir));
+            }
+        }
+
+        best.map(|(_, dir)| dir)
+    }
+}
+
+fn direction_to(moves: &[(Direction, Position)], cell: Position) -> Option<Direction> {
+    moves.iter().find(|(_, to)| *to == cell).map(|(dir, _)| *dir)
+}
+
+// A board as seen by a snake that moves one cell per tick: each cell is free
+// from some tick on, the next tick being 1. The cells of snakes free up as
+// their tails move on.
+#[derive(Clone)]
+struct Board {
+    width: usize,
+    height: usize,
+    walls: Walls,
+    free_from: Vec<usize>,
+    apple: Option<Position>,
+}
+
+// Where a snake would be once it followed a path to the apple
+struct Eaten {
+    board: Board,
+    body: Vec<Position>,
+}
+
+impl Board {
+    fn new(engine: &Engine) -> Self {
+        let grid = engine.grid();
+        let mut board = Self {
+            width: grid.width(),
+            height: grid.height(),
+            walls: grid.walls(),
+            free_from: (0..grid.size())
+                .map(|i| if grid[i] == Cell::Wall { TAKEN } else { 0 })
+                .collect(),
+            apple: None,
+        };
+        board.apple = (0..grid.size())
+            .find(|&i| grid[i] == Cell::Apple)
+            .map(|i| board.position(i));
+        for (i, snake) in engine.snakes().iter().enumerate() {
+            let body = snake.segments().copied().collect::<Vec<_>>();
+            for (j, cell) in body.iter().enumerate() {
+                let index = board.index(*cell);
+                board.free_from[index] = if engine.is_alive(i) {
+                    free_from(body.len(), j, snake.is_growing())
+                } else {
+                    TAKEN
+                };
+            }
+        }
+        board
+    }
+
+    fn position(&self, i: usize) -> Position {
+        Position { x: i % self.width, y: i / self.width }
+    }
+
+    fn index(&self, position: Position) -> usize {
+        position.y*self.width + position.x
+    }
+
+    fn neighbors(&self, position: Position) -> impl Iterator<Item = (Direction, Position)> + '_ {
+        DIRECTIONS.iter().filter_map(move |dir| {
+            let next = match self.walls {
+                Walls::Solid => position.move_in_direction(dir).ok(),
+                Walls::Open => Some(position.wrap_in_direction(dir, self.width, self.height)),
+            };
+            next.filter(|next| next.x < self.width && next.y < self.height)
+                .map(|next| (*dir, next))
+        })
+    }
+
+    // Cells that can be moved to on the next tick.
+    fn moves(&self, position: Position) -> Vec<(Direction, Position)> {
+        self.neighbors(position)
+            .filter(|(_, cell)| self.free_from[self.index(*cell)] <= 1)
+            .collect()
+    }
+
+    // The earliest tick each cell can be reached on from `from`, starting
+    // with one of `first_moves`, and the cell it is reached from.
+    fn explore(
+        &self,
+        from: Position,
+        first_moves: &[(Direction, Position)],
+    ) -> Vec<Option<(usize, Position)>> {
+        let mut reached = vec![None; self.free_from.len()];
+        let mut queue = VecDeque::new();
+        for &(_, cell) in first_moves {
+            reached[self.index(cell)] = Some((1, from));
+            queue.push_back((cell, 1));
+        }
+
+        while let Some((cell, tick)) = queue.pop_front() {
+            // Growing would hold up the tail, so paths end at the apple
+            if Some(cell) == self.apple {
+                continue;
+            }
+            // Turning back is not possible, which only matters for `from`
+            let back = reached[self.index(cell)].map(|(_, before)| before);
+            for (_, next) in self.neighbors(cell) {
+                let i = self.index(next);
+                if reached[i].is_none() && Some(next) != back && self.free_from[i] <= tick + 1 {
+                    reached[i] = Some((tick + 1, cell));
+                    queue.push_back((next, tick + 1));
+                }
+            }
+        }
+
+        reached
+    }
+
+    // Shortest path from `from` to `to`, without `from` itself.
+    fn path(
+        &self,
+        from: Position,
+        to: Position,
+        first_moves: &[(Direction, Position)],
+    ) -> Option<Vec<Position>> {
+        let reached = self.explore(from, first_moves);
+        reached[self.index(to)]?;
+
+        let mut path = vec![to];
+        // The path may pass `from` again once the snake has moved on
+        while let Some((tick, before)) = reached[self.index(path[path.len() - 1])] {
+            if tick == 1 {
+                break;
+            }
+            path.push(before);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    // Cells that can be reached from `from` at all.
+    fn room(&self, from: Position, first_moves: &[(Direction, Position)]) -> usize {
+        self.explore(from, first_moves).iter().filter(|cell| cell.is_some()).count()
+    }
+
+    // Whether a snake with `body` is unlikely to be walled in. Once its head
+    // reaches a cell its body has left, the next cell of the body is left
+    // just in time for the head to follow, all the way round. Failing that,
+    // plenty of free room should last until the body is gone.
+    fn is_safe(&self, body: &[Position], first_moves: &[(Direction, Position)]) -> bool {
+        let reached = self.explore(body[0], first_moves);
+        let free = (0..reached.len())
+            .filter(|&i| reached[i].is_some() && self.free_from[i] == 0)
+            .count();
+        free > 2*body.len() || body.iter().skip(1).any(|cell| reached[self.index(*cell)].is_some())
+    }
+
+    // Moves between `from` and `to` ignoring what is in the way.
+    fn distance(&self, from: Position, to: Position) -> usize {
+        let dx = from.x.abs_diff(to.x);
+        let dy = from.y.abs_diff(to.y);
+        match self.walls {
+            Walls::Solid => dx + dy,
+            Walls::Open => dx.min(self.width - dx) + dy.min(self.height - dy),
+        }
+    }
+
+    // The board once a snake with `body` has followed `path` to the apple at
+    // its end. A snake that is `growing` is one segment longer by then.
+    fn after_eating(&self, body: &[Position], growing: bool, path: &[Position]) -> Eaten {
+        let mut board = self.clone();
+        board.apple = None;
+        for tick in board.free_from.iter_mut() {
+            if *tick != TAKEN {
+                *tick = tick.saturating_sub(path.len());
+            }
+        }
+        for cell in body {
+            let index = board.index(*cell);
+            board.free_from[index] = 0;
+        }
+        let moved = path.iter()
+            .rev()
+            .chain(body.iter())
+            .take(body.len() + usize::from(growing))
+            .copied()
+            .collect::<Vec<_>>();
+        // The next apple may turn up in the way and hold up the tail again,
+        // so the body is taken to leave its cells a tick later still
+        for (j, cell) in moved.iter().enumerate() {
+            let index = board.index(*cell);
+            board.free_from[index] = free_from(moved.len(), j, true) + 1;
+        }
+
+        Eaten {
+            board,
+            body: moved,
+        }
+    }
+}
+
+// The first tick the cell of segment `j`, counted from the head, of a snake
+// of `len` segments can be moved to. The tail leaves its cell on the next
+// tick, or on the one after when the snake grows, but cells are only free
+// on the tick after they were left.
+fn free_from(len: usize, j: usize, growing: bool) -> usize {
+    len - j + usize::from(growing) + 1
+}
+
+// Follows a Hamiltonian cycle, a closed path through every cell of the
+// board, which the snake can trace forever without running into itself, so
+// that it always fills the board. While the snake is short, it cuts across
+// the cycle towards the apple, but never past its tail. The cycle is made
+// for the board the pilot first steers on. Boards with walls, or with an
+// odd number of cells, have none, and the snake is left to a `Pathfinder`.
+#[derive(Default)]
+pub struct Hamiltonian {
+    // The cells in the order they are visited, and the place of each cell
+    // in that order
+    cycle: Vec<Position>,
+    order: Vec<usize>,
+    fallback: Option<Pathfinder>,
+}
+
+// Free cells kept between the head and the tail after a shortcut, as the
+// snake may eat more apples before its tail makes up for them
+const SHORTCUT_MARGIN: usize = 4;
+
+impl Hamiltonian {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A cycle through a board of `width` by `height`, winding along the rows
+    // and going back up the first column.
+    fn cycle(width: usize, height: usize) -> Option<Vec<Position>> {
+        if width % 2 == 1 && height % 2 == 1 {
+            return None;
+        }
+        if height % 2 == 1 {
+            // Columns work as well as rows
+            let cycle = Self::cycle(height, width)?;
+            return Some(cycle.into_iter()
+                .map(|Position { x, y }| Position { x: y, y: x })
+                .collect());
+        }
+        if width < 2 {
+            return None;
+        }
+
+        let mut cycle = (0..width).map(|x| Position { x, y: 0 }).collect::<Vec<_>>();
+        for y in 1..height {
+            if y % 2 == 1 {
+                cycle.extend((1..width).rev().map(|x| Position { x, y }));
+            } else {
+                cycle.extend((1..width).map(|x| Position { x, y }));
+            }
+        }
+        cycle.extend((1..height).rev().map(|y| Position { x: 0, y }));
+        Some(cycle)
+    }
+
+    fn plan(&mut self, engine: &Engine, player: usize) {
+        let grid = engine.grid();
+        let cycle = Self::cycle(grid.width(), grid.height())
+            .filter(|_| !grid.contains(Cell::Wall));
+        let mut cycle = match cycle {
+            Some(cycle) => cycle,
+            None => {
+                self.fallback = Some(Pathfinder::new());
+                return;
+            },
+        };
+
+        // The cycle can be followed either way round, but the snake cannot
+        // turn back
+        let snake = &engine.snakes()[player];
+        let head = cycle.iter().position(|&cell| cell == snake.head()).unwrap_or(0);
+        let next = cycle[(head + 1) % cycle.len()];
+        let backwards = DIRECTIONS.iter().any(|dir| {
+            are_opposite(dir, &snake.dir()) && snake.head().move_in_direction(dir) == Ok(next)
+        });
+        if backwards {
+            cycle.reverse();
+        }
+
+        self.order = vec![0; cycle.len()];
+        for (i, cell) in cycle.iter().enumerate() {
+            self.order[cell.y*grid.width() + cell.x] = i;
+        }
+        self.cycle = cycle;
+    }
+
+    // Cells from `from` to `to` along the cycle.
+    fn distance(&self, width: usize, from: Position, to: Position) -> usize {
+        let n = self.cycle.len();
+        (self.order[to.y*width + to.x] + n - self.order[from.y*width + from.x]) % n
+    }
+}
+
+impl Pilot for Hamiltonian {
+    fn steer(&mut self, engine: &Engine, player: usize) -> Option<Direction> {
+        if self.cycle.is_empty() && self.fallback.is_none() {
+            self.plan(engine, player);
+        }
+        if let Some(fallback) = self.fallback.as_mut() {
+            return fallback.steer(engine, player);
+        }
+
+        let grid = engine.grid();
+        let width = grid.width();
+        let n = self.cycle.len();
+        let snake = &engine.snakes()[player];
+        let head = snake.head();
+        let len = snake.segments().count();
+        let next = self.cycle[(self.order[head.y*width + head.x] + 1) % n];
+
+        // Cells ahead of the head up to the tail are all free, and stay so
+        // if the head skips some of them
+        let mut to = next;
+        if 2*len < n {
+            let to_tail = match self.distance(width, head, snake.tail()) {
+                0 => n,
+                to_tail => to_tail,
+            };
+            let margin = SHORTCUT_MARGIN + usize::from(snake.is_growing());
+            let apple = (0..grid.size()).find(|&i| grid[i] == Cell::Apple);
+            let to_apple = apple.map_or(n, |i| {
+                self.distance(width, head, Position { x: i % width, y: i / width })
+            });
+            for dir in DIRECTIONS.iter().filter(|dir| !are_opposite(dir, &snake.dir())) {
+                let cell = match head.move_in_direction(dir) {
+                    Ok(cell) if cell.x < width && cell.y < grid.height() => cell,
+                    _ => continue,
+                };
+                let skip = self.distance(width, head, cell);
+                let free = !matches!(grid[cell.y*width + cell.x], Cell::Snake | Cell::Wall);
+                if free
+                    && skip <= to_apple
+                    && skip + margin < to_tail
+                    && skip > self.distance(width, head, to)
+                {
+                    to = cell;
+                }
+            }
+        }
+
+        DIRECTIONS.iter()
+            .copied()
+            .find(|dir| head.move_in_direction(dir) == Ok(to))
+    }
+}
+
+// The outcome of a game played by pilots alone.
+#[derive(Debug, PartialEq)]
+pub struct Autoplay {
+    pub state: State,
+    pub ticks: usize,
+    pub points: usize,
+}
+
+// Plays `engine` to its end, or for at most `max_ticks`, with one pilot per
+// snake. Used for benchmarks and tests, without a terminal.
+pub fn autoplay(engine: &mut Engine, pilots: &mut [Box<dyn Pilot>], max_ticks: usize) -> Autoplay {
+    let mut ticks = 0;
+    while engine.state() == State::Playing && ticks < max_ticks {
+        let inputs = pilots.iter_mut()
+            .enumerate()
+            .map(|(i, pilot)| pilot.steer(engine, i).into_iter().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let inputs = inputs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        engine.tick_players(&inputs);
+        ticks += 1;
+    }
+
+    Autoplay {
+        state: engine.state(),
+        ticks,
+        points: engine.points(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    fn steer(text: &str) -> Option<Direction> {
+        let level = Level::parse(text).unwrap();
+        let engine = Engine::new(&level, Walls::Solid, 0);
+        Pathfinder::new().steer(&engine, 0)
+    }
+
+    #[test]
+    fn test_steer_heads_for_the_apple() {
+        assert_eq!(steer("\
+..<..
+....A
+"), Some(Direction::Down));
+        assert_eq!(steer("\
+..A..
+.....
+..>..
+"), Some(Direction::Up));
+    }
+
+    #[test]
+    fn test_steer_goes_around_walls() {
+        assert_eq!(steer("\
+..A..
+.###.
+..^..
+"), Some(Direction::Left));
+    }
+
+    #[test]
+    fn test_steer_when_boxed_in_then_takes_the_only_way_out() {
+        assert_eq!(steer("\
+A#...
+.#...
+.#^#.
+.###.
+"), Some(Direction::Up));
+    }
+
+    #[test]
+    fn test_steer_does_not_run_into_another_head() {
+        // Left would be just as short a way to the apple
+        let level = Level::parse("\
+....^
+.....
+.A...
+").unwrap();
+        let engine = Engine::with_spawns(&level, Walls::Solid, 0, &[
+            (Position { x: 2, y: 1 }, Direction::Left),
+            (Position { x: 0, y: 1 }, Direction::Right),
+        ]);
+
+        assert_eq!(Pathfinder::new().steer(&engine, 0), Some(Direction::Down));
+    }
+
+    #[test]
+    fn test_free_from_when_growing_then_tail_stays_a_tick_longer() {
+        assert_eq!(free_from(3, 2, false), 2);
+        assert_eq!(free_from(3, 2, true), 3);
+        assert_eq!(free_from(3, 0, false), 4);
+    }
+
+    #[test]
+    fn test_autoplay_fills_most_of_a_small_board() {
+        for seed in 0..10 {
+            let mut engine = Engine::new(&Level::empty(6, 6), Walls::Solid, seed);
+            let mut pilots: Vec<Box<dyn Pilot>> = vec![Box::new(Pathfinder::new())];
+
+            let result = autoplay(&mut engine, &mut pilots, 10_000);
+
+            assert!(result.points >= 20, "seed {}: {:?}", seed, result);
+        }
+    }
+
+    #[test]
+    fn test_cycle_visits_every_cell_once_then_comes_back() {
+        for (width, height) in [(2, 2), (6, 4), (5, 4), (4, 5), (7, 10)] {
+            let cycle = Hamiltonian::cycle(width, height).unwrap();
+
+            assert_eq!(cycle.len(), width*height);
+            for (i, cell) in cycle.iter().enumerate() {
+                assert!(cell.x < width && cell.y < height);
+                assert!(!cycle[..i].contains(cell));
+                let next = cycle[(i + 1) % cycle.len()];
+                assert_eq!(cell.x.abs_diff(next.x) + cell.y.abs_diff(next.y), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cycle_when_the_board_cannot_have_one_then_none() {
+        assert_eq!(Hamiltonian::cycle(5, 3), None);
+        assert_eq!(Hamiltonian::cycle(1, 4), None);
+    }
+
+    #[test]
+    fn test_hamiltonian_when_autoplaying_then_fills_the_board() {
+        for walls in [Walls::Solid, Walls::Open] {
+            for seed in 0..10 {
+                let mut engine = Engine::new(&Level::empty(10, 8), walls, seed);
+                let mut pilots: Vec<Box<dyn Pilot>> = vec![Box::new(Hamiltonian::new())];
+
+                let result = autoplay(&mut engine, &mut pilots, 100_000);
+
+                assert_eq!(result.state, State::Won, "seed {}: {:?}", seed, result);
+                assert_eq!(result.points, 80);
+                assert_eq!(engine.snake().segments().count(), 80);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hamiltonian_when_the_board_is_large_then_fills_it() {
+        let mut engine = Engine::new(&Level::empty(30, 20), Walls::Solid, 0);
+        let mut pilots: Vec<Box<dyn Pilot>> = vec![Box::new(Hamiltonian::new())];
+
+        let result = autoplay(&mut engine, &mut pilots, 1_000_000);
+
+        assert_eq!(result.state, State::Won, "{:?}", result);
+        assert_eq!(result.points, 600);
+    }
+
+    #[test]
+    fn test_hamiltonian_when_there_is_no_cycle_then_falls_back() {
+        let level = Level::parse("\
+#.....
+#..>.A
+#.....").unwrap();
+        let engine = Engine::new(&level, Walls::Solid, 0);
+        let mut pilot = Hamiltonian::new();
+
+        assert_eq!(pilot.steer(&engine, 0), Some(Direction::Right));
+        assert!(pilot.fallback.is_some());
+    }
+}
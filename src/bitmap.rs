@@ -1,8 +1,16 @@
 use std::ops::{AddAssign, Index};
 use rusty_check::rusty_check;
 
+use crate::glyphs::Glyphs;
+
 const BITMAP_SIZE: usize = 5;
 
+// Positions of the bits, laid out as a cross
+const UP: usize = 0;
+const LEFT: usize = 1;
+const RIGHT: usize = 3;
+const DOWN: usize = 4;
+
 #[derive(Debug, PartialEq)]
 pub enum State {
     E, // Empty
@@ -42,82 +50,24 @@ impl Bitmap {
         Self { bits }
     }
 
-    pub fn render(&self) -> String {
-        use State::*;
-
-        let output = match self.bits {
-            [
-                   E,
-                E, E, E,
-                   E,
-            ] => "  ",
-            [
-                   E,
-                N, N, N,
-                   E,
-            ] => "──",
-            [
-                   N,
-                E, N, E,
-                   N,
-            ] => "│ ",
-            [
-                   N,
-                E, N, N,
-                   E,
-            ] => "└─",
-            [
-                   E,
-                E, N, N,
-                   N,
-            ] => "┌─",
-            [
-                   E,
-                N, N, E,
-                   N,
-            ] => "┐ ",
-            [
-                   N,
-                N, N, E,
-                   E,
-            ] => "┘ ",
-            [
-                   N,
-                N, N, N,
-                   E,
-            ] => "┴─",
-            [
-                   N,
-                E, N, N,
-                   N,
-            ] => "├─",
-            [
-                   E,
-                N, N, N,
-                   N,
-            ] => "┬─",
-            [
-                   N,
-                N, N, E,
-                   N,
-            ] => "┤ ",
-            [
-                   N,
-                N, N, N,
-                   N,
-            ] => "┼─",
-            _ => {
-                todo!("Probably not needed");
-            }
-        };
+    // The glyph is chosen by the lines leaving the center, so the center
+    // itself does not matter. A line to the right is continued in the
+    // second character, to reach the next corner.
+    pub fn render(&self, glyphs: &Glyphs) -> String {
+        let arm = |i: usize| self.bits[i] == State::N;
 
-        output.to_string()
+        let mut output = String::new();
+        output.push(glyphs.line(arm(UP), arm(LEFT), arm(RIGHT), arm(DOWN)));
+        output.push(if arm(RIGHT) { glyphs.horizontal() } else { ' ' });
+
+        output
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::glyphs::GLYPH_SETS;
     use State::*;
 
     #[test]
@@ -175,6 +125,52 @@ mod tests {
             assert_eq!(bitmap[i], expected[i]);
         }
     }
+
+    #[test]
+    fn test_render() {
+        let cases = [
+            ([E, E, E, E, E], "  "),
+            ([E, N, N, N, E], "──"),
+            ([N, E, N, E, N], "│ "),
+            ([N, N, N, N, N], "┼─"),
+            ([N, E, N, E, E], "╵ "),
+            ([E, N, N, E, E], "╴ "),
+            ([E, E, N, N, E], "╶─"),
+            ([E, E, N, E, N], "╷ "),
+            ([N, E, E, E, E], "╵ "),
+        ];
+
+        for (bits, expected) in cases {
+            assert_eq!(Bitmap::from(bits).render(Glyphs::default_set()), expected);
+        }
+    }
+
+    #[test]
+    fn test_render_every_combination() {
+        for set in GLYPH_SETS.iter() {
+            let mut glyphs = Vec::new();
+            for n in 0..1 << BITMAP_SIZE {
+                let bits = std::array::from_fn(|i| if n & 1 << i != 0 { N } else { E });
+                let right = bits[RIGHT] == N;
+                let output = Bitmap::from(bits).render(set);
+                let chars = output.chars().collect::<Vec<_>>();
+
+                assert_eq!(chars.len(), 2, "{}", set.name);
+                assert_eq!(chars[1], if right { set.horizontal() } else { ' ' }, "{}", set.name);
+                if n & 1 << 2 != 0 {
+                    glyphs.push(chars[0]);
+                }
+            }
+
+            // Each set of lines has a glyph of its own, where the set has
+            // enough glyphs for that
+            if matches!(set.name, "light" | "heavy" | "rounded") {
+                glyphs.sort();
+                glyphs.dedup();
+                assert_eq!(glyphs.len(), 16, "{}", set.name);
+            }
+        }
+    }
 }
 rusty_check! {
     case test_in_project {
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+// In progressive mode the snake speeds up every `POINTS_PER_LEVEL` points,
+// each level shortening the tick by `LEVEL_SPEEDUP_PERCENT` of the base
+// duration, up to `MAX_LEVEL`.
+const POINTS_PER_LEVEL: usize = 5;
+const LEVEL_SPEEDUP_PERCENT: u32 = 7;
+const MAX_LEVEL: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Insane,
+}
+
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            "insane" => Some(Difficulty::Insane),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+            Difficulty::Insane => "insane",
+        }
+    }
+
+    fn tick_duration(self) -> Duration {
+        let millis = match self {
+            Difficulty::Easy => 150,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 70,
+            Difficulty::Insane => 45,
+        };
+        Duration::from_millis(millis)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Speed {
+    pub difficulty: Difficulty,
+    pub progressive: bool,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Normal,
+            progressive: false,
+        }
+    }
+}
+
+impl Speed {
+    // Starts at 1 and only grows in progressive mode.
+    pub fn level(&self, points: usize) -> usize {
+        if !self.progressive {
+            return 1;
+        }
+        (1 + points/POINTS_PER_LEVEL).min(MAX_LEVEL)
+    }
+
+    pub fn tick_duration(&self, points: usize) -> Duration {
+        let speedup = LEVEL_SPEEDUP_PERCENT*(self.level(points) as u32 - 1);
+        self.difficulty.tick_duration()*(100 - speedup)/100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name_is_inverse_of_name() {
+        for difficulty in [
+            Difficulty::Easy,
+            Difficulty::Normal,
+            Difficulty::Hard,
+            Difficulty::Insane,
+        ] {
+            assert_eq!(Difficulty::from_name(difficulty.name()), Some(difficulty));
+        }
+        assert_eq!(Difficulty::from_name("nightmare"), None);
+    }
+
+    #[test]
+    fn test_tick_duration_is_constant_when_not_progressive() {
+        let speed = Speed::default();
+
+        assert_eq!(speed.level(1000), 1);
+        assert_eq!(speed.tick_duration(0), Duration::from_millis(100));
+        assert_eq!(speed.tick_duration(1000), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_tick_duration_shrinks_as_points_grow_when_progressive() {
+        let speed = Speed {
+            difficulty: Difficulty::Normal,
+            progressive: true,
+        };
+
+        assert_eq!(speed.level(4), 1);
+        assert_eq!(speed.tick_duration(4), Duration::from_millis(100));
+        assert_eq!(speed.level(5), 2);
+        assert_eq!(speed.tick_duration(5), Duration::from_millis(93));
+        assert_eq!(speed.level(1000), MAX_LEVEL);
+        assert_eq!(speed.tick_duration(1000), Duration::from_millis(37));
+    }
+}
@@ -0,0 +1,513 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::direction::Direction;
+use crate::grid::{ Cell, Grid, Walls };
+use crate::level::Level;
+use crate::position::Position;
+use crate::snake::{ Snake, Status };
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Playing,
+    GameOver,
+    Won,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    Ate,
+    Died,
+    AppleSpawned(Position),
+    Won,
+}
+
+// The rules of the game without any notion of time or terminal. A driver
+// calls `tick` once per simulation step with the inputs gathered since the
+// previous step. All randomness comes from an RNG seeded with `seed`, so
+// the same seed and the same inputs always produce the same game. The RNG
+// is named rather than `StdRng`, whose output may change with any version
+// of rand, as seeds and replays are shared. It is the one `StdRng` was when
+// the first replays were recorded.
+//
+// With several snakes, the game is over once at most one of them is left
+// alive. The bodies of dead snakes stay on the board.
+pub struct Engine {
+    grid: Grid,
+    snakes: Vec<Snake>,
+    alive: Vec<bool>,
+    scores: Vec<usize>,
+    state: State,
+    seed: u64,
+    rng: ChaCha12Rng,
+}
+
+impl Engine {
+    pub fn new(level: &Level, walls: Walls, seed: u64) -> Self {
+        Self::with_spawns(level, walls, seed, &[(level.spawn, level.dir)])
+    }
+
+    // Starts one snake at each of `spawns`, ignoring the level's own.
+    pub fn with_spawns(
+        level: &Level,
+        walls: Walls,
+        seed: u64,
+        spawns: &[(Position, Direction)],
+    ) -> Self {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let mut grid = Grid::new(level.width, level.height);
+        grid.set_walls(walls);
+        for (i, cell) in level.cells.iter().enumerate() {
+            grid[i] = *cell;
+        }
+        let width = grid.width();
+        for (origin, _) in spawns {
+            grid[origin.y*width + origin.x] = Cell::Snake;
+        }
+        if !level.cells.contains(&Cell::Apple) {
+            grid.gen_apple(&mut rng);
+        }
+        let snakes = spawns.iter()
+            .map(|&(origin, dir)| Snake::new(origin, dir))
+            .collect();
+        Self {
+            grid,
+            snakes,
+            alive: vec![true; spawns.len()],
+            scores: vec![0; spawns.len()],
+            state: State::Playing,
+            seed,
+            rng,
+        }
+    }
+
+    pub fn tick(&mut self, inputs: &[Direction]) -> (State, Vec<Event>) {
+        self.tick_players(&[inputs])
+    }
+
+    // Like `tick`, with the inputs of each snake in turn.
+    pub fn tick_players(&mut self, inputs: &[&[Direction]]) -> (State, Vec<Event>) {
+        let mut events = Vec::new();
+        if self.state != State::Playing {
+            return (self.state, events);
+        }
+
+        for (snake, dirs) in self.snakes.iter_mut().zip(inputs) {
+            for dir in dirs.iter() {
+                snake.queue_dir(*dir);
+            }
+        }
+
+        // Every move is checked against the board as it was before any snake
+        // moved, so that no snake gets ahead by being updated first. Heads
+        // meeting on the same cell kill both snakes.
+        let width = self.grid.width();
+        let targets = self.snakes.iter()
+            .map(|snake| snake.next_head(&self.grid))
+            .collect::<Vec<_>>();
+        let doomed = (0..self.snakes.len())
+            .map(|i| match targets[i] {
+                Some(target) => {
+                    let cell = self.grid[target.y*width + target.x];
+                    let head_on = (0..self.snakes.len())
+                        .any(|j| j != i && self.alive[j] && targets[j] == targets[i]);
+                    matches!(cell, Cell::Snake | Cell::Wall) || head_on
+                },
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        for (i, doomed) in doomed.into_iter().enumerate() {
+            if !self.alive[i] {
+                continue;
+            }
+            let status = if doomed {
+                &Status::Died
+            } else {
+                self.snakes[i].update(&mut self.grid)
+            };
+            match status {
+                Status::Ate => {
+                    self.scores[i] += 1;
+                    events.push(Event::Ate);
+                    match self.grid.gen_apple(&mut self.rng) {
+                        Some(apple_pos) => {
+                            events.push(Event::AppleSpawned(apple_pos));
+                        },
+                        None if !self.grid.contains(Cell::Apple) => {
+                            // The snakes have filled the whole board
+                            self.state = State::Won;
+                            events.push(Event::Won);
+                        },
+                        None => (),
+                    }
+                },
+                Status::Died => {
+                    self.alive[i] = false;
+                    events.push(Event::Died);
+                },
+                Status::Moved => (),
+            }
+        }
+        self.check_survivors();
+
+        (self.state, events)
+    }
+
+    // Takes a snake out of the game, e.g. when its player left.
+    pub fn kill(&mut self, player: usize) {
+        self.alive[player] = false;
+        self.check_survivors();
+    }
+
+    fn check_survivors(&mut self) {
+        let alive = self.alive.iter().filter(|alive| **alive).count();
+        let last = if self.snakes.len() > 1 { 1 } else { 0 };
+        if self.state == State::Playing && alive <= last {
+            self.state = State::GameOver;
+        }
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    // The first snake, the only one in a single-player game
+    pub fn snake(&self) -> &Snake {
+        &self.snakes[0]
+    }
+
+    pub fn snakes(&self) -> &[Snake] {
+        &self.snakes
+    }
+
+    // Points of all the snakes together
+    pub fn points(&self) -> usize {
+        self.scores.iter().sum()
+    }
+
+    pub fn scores(&self) -> &[usize] {
+        &self.scores
+    }
+
+    pub fn is_alive(&self, player: usize) -> bool {
+        self.alive[player]
+    }
+
+    // The player who won a game with several snakes once it is over: the
+    // last one alive, or else the one with the most points. `None` while
+    // the game goes on or when it is a draw.
+    pub fn winner(&self) -> Option<usize> {
+        if self.state == State::Playing {
+            return None;
+        }
+
+        let alive = (0..self.snakes.len())
+            .filter(|&i| self.alive[i])
+            .collect::<Vec<_>>();
+        if let [player] = alive[..] {
+            return Some(player);
+        }
+        let best = *self.scores.iter().max()?;
+        let mut leaders = (0..self.scores.len()).filter(|&i| self.scores[i] == best);
+        match (leaders.next(), leaders.next()) {
+            (Some(player), None) => Some(player),
+            _ => None,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(text: &str) -> Engine {
+        Engine::new(&Level::parse(text).unwrap(), Walls::Solid, 0)
+    }
+
+    #[test]
+    fn test_new_places_the_snake_at_the_spawn() {
+        let engine = engine("\
+A...
+..^.
+");
+
+        assert_eq!(engine.grid()[6], Cell::Snake);
+        assert_eq!(engine.grid()[0], Cell::Apple);
+        assert_eq!(engine.state(), State::Playing);
+    }
+
+    #[test]
+    fn test_tick_when_nothing_happens_then_returns_no_events() {
+        let mut engine = engine("\
+A...
+....
+..^.
+");
+
+        assert_eq!(engine.tick(&[]), (State::Playing, vec![]));
+        assert_eq!(engine.grid()[6], Cell::Snake);
+    }
+
+    #[test]
+    fn test_tick_applies_inputs_before_moving() {
+        let mut engine = engine("\
+A...
+..^.
+");
+
+        engine.tick(&[Direction::Left]);
+
+        assert_eq!(engine.grid()[5], Cell::Snake);
+        assert_eq!(engine.grid()[6], Cell::Empty);
+    }
+
+    #[test]
+    fn test_tick_applies_one_turn_per_tick() {
+        let mut engine = engine("\
+A...
+.^..
+....
+");
+
+        engine.tick(&[Direction::Right, Direction::Down]);
+
+        assert_eq!(engine.grid()[6], Cell::Snake);
+
+        engine.tick(&[]);
+
+        assert_eq!(engine.grid()[6], Cell::Empty);
+        assert_eq!(engine.grid()[10], Cell::Snake);
+    }
+
+    #[test]
+    fn test_tick_when_snake_eats_then_scores_and_spawns_an_apple() {
+        let mut engine = engine("\
+..A.
+..^.
+");
+
+        let (state, events) = engine.tick(&[]);
+
+        assert_eq!(state, State::Playing);
+        assert_eq!(engine.points(), 1);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], Event::Ate);
+        match events[1] {
+            Event::AppleSpawned(Position { x, y }) => {
+                assert_eq!(engine.grid()[y*4 + x], Cell::Apple);
+            },
+            _ => panic!("Expected an apple to be spawned"),
+        }
+    }
+
+    #[test]
+    fn test_same_seed_and_inputs_produce_the_same_game() {
+        let level = Level::empty(12, 8);
+        let inputs = [
+            vec![],
+            vec![Direction::Left],
+            vec![],
+            vec![Direction::Down],
+            vec![Direction::Right, Direction::Up],
+        ];
+        let mut engines = [
+            Engine::new(&level, Walls::Open, 42),
+            Engine::new(&level, Walls::Open, 42),
+        ];
+
+        for _ in 0..20 {
+            for tick_inputs in inputs.iter() {
+                let [first, second] = &mut engines;
+                assert_eq!(first.tick(tick_inputs), second.tick(tick_inputs));
+                assert_eq!(first.grid().render(), second.grid().render());
+            }
+        }
+    }
+
+    #[test]
+    fn test_different_seeds_place_apples_differently() {
+        let level = Level::empty(36, 20);
+        let apples = (0..8)
+            .map(|seed| {
+                let engine = Engine::new(&level, Walls::Solid, seed);
+                let grid = engine.grid();
+                (0..grid.size()).position(|i| grid[i] == Cell::Apple)
+            })
+            .collect::<Vec<_>>();
+
+        assert!(apples.iter().any(|apple| apple != &apples[0]));
+    }
+
+    #[test]
+    fn test_seeds_place_apples_where_they_always_did() {
+        // Any change here breaks every shared seed and replay
+        let level = Level::empty(36, 20);
+        let apples = (0..4)
+            .map(|seed| {
+                let engine = Engine::new(&level, Walls::Solid, seed);
+                let grid = engine.grid();
+                (0..grid.size()).position(|i| grid[i] == Cell::Apple)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(apples, [Some(526), Some(701), Some(12), Some(68)]);
+    }
+
+    #[test]
+    fn test_tick_when_board_is_full_then_game_is_won() {
+        let level = Level::parse("\
+#A
+#^
+").unwrap();
+        let mut engine = Engine::new(&level, Walls::Open, 0);
+
+        assert_eq!(
+            engine.tick(&[]),
+            (State::Playing, vec![
+                Event::Ate,
+                Event::AppleSpawned(Position { x: 1, y: 1 }),
+            ]),
+        );
+        assert_eq!(
+            engine.tick(&[]),
+            (State::Won, vec![Event::Ate, Event::Won]),
+        );
+        assert_eq!(engine.points(), 2);
+        assert_eq!(engine.tick(&[]), (State::Won, vec![]));
+    }
+
+    #[test]
+    fn test_tick_when_snake_dies_then_game_is_over() {
+        let mut engine = engine("\
+A.#.
+..^.
+");
+
+        assert_eq!(engine.tick(&[]), (State::GameOver, vec![Event::Died]));
+        assert_eq!(engine.tick(&[Direction::Left]), (State::GameOver, vec![]));
+        assert_eq!(engine.grid()[6], Cell::Snake);
+    }
+
+    fn two_player_engine(text: &str, spawns: &[(Position, Direction)]) -> Engine {
+        Engine::with_spawns(&Level::parse(text).unwrap(), Walls::Solid, 0, spawns)
+    }
+
+    #[test]
+    fn test_tick_players_moves_every_snake() {
+        let mut engine = two_player_engine("\
+A.....
+^.....
+......
+", &[
+            (Position { x: 1, y: 2 }, Direction::Up),
+            (Position { x: 4, y: 2 }, Direction::Up),
+        ]);
+
+        let (state, _) = engine.tick_players(&[&[Direction::Right], &[Direction::Left]]);
+
+        assert_eq!(state, State::Playing);
+        assert_eq!(engine.snakes()[0].head(), Position { x: 2, y: 2 });
+        assert_eq!(engine.snakes()[1].head(), Position { x: 3, y: 2 });
+    }
+
+    #[test]
+    fn test_tick_players_when_heads_meet_then_both_die() {
+        let mut engine = two_player_engine("\
+A....
+^....
+", &[
+            (Position { x: 1, y: 1 }, Direction::Right),
+            (Position { x: 3, y: 1 }, Direction::Left),
+        ]);
+
+        assert_eq!(
+            engine.tick_players(&[&[], &[]]),
+            (State::GameOver, vec![Event::Died, Event::Died]),
+        );
+        assert!(!engine.is_alive(0));
+        assert!(!engine.is_alive(1));
+        assert_eq!(engine.winner(), None);
+    }
+
+    #[test]
+    fn test_tick_players_when_head_hits_a_body_then_only_that_snake_dies() {
+        // The second snake eats, grows across the path of the first one, and
+        // the first one runs into its body
+        let mut engine = two_player_engine("\
+.....
+..A..
+^....
+", &[
+            (Position { x: 0, y: 1 }, Direction::Right),
+            (Position { x: 2, y: 0 }, Direction::Down),
+        ]);
+
+        assert_eq!(engine.tick_players(&[&[], &[]]).0, State::Playing);
+        assert_eq!(engine.tick_players(&[&[], &[]]).0, State::GameOver);
+        assert!(!engine.is_alive(0));
+        assert!(engine.is_alive(1));
+        assert_eq!(engine.winner(), Some(1));
+    }
+
+    #[test]
+    fn test_tick_players_counts_points_per_snake() {
+        let mut engine = two_player_engine("\
+.....
+.....
+A^...
+", &[
+            (Position { x: 0, y: 1 }, Direction::Down),
+            (Position { x: 4, y: 1 }, Direction::Down),
+        ]);
+
+        engine.tick_players(&[&[], &[]]);
+
+        assert_eq!(engine.scores(), [1, 0]);
+        assert_eq!(engine.points(), 1);
+        assert_eq!(engine.winner(), None);
+    }
+
+    #[test]
+    fn test_tick_players_when_one_of_three_snakes_dies_then_game_goes_on() {
+        let mut engine = two_player_engine("\
+.A.....
+.......
+^......
+", &[
+            (Position { x: 0, y: 0 }, Direction::Up),
+            (Position { x: 3, y: 1 }, Direction::Down),
+            (Position { x: 6, y: 1 }, Direction::Down),
+        ]);
+
+        assert_eq!(engine.tick_players(&[&[], &[], &[]]).0, State::Playing);
+        assert!(!engine.is_alive(0));
+        assert_eq!(engine.tick_players(&[&[], &[], &[]]).0, State::GameOver);
+        assert_eq!(engine.winner(), None);
+    }
+
+    #[test]
+    fn test_kill_when_one_snake_is_left_then_it_wins() {
+        let mut engine = two_player_engine("\
+A.....
+^.....
+......
+", &[
+            (Position { x: 1, y: 2 }, Direction::Up),
+            (Position { x: 4, y: 2 }, Direction::Up),
+        ]);
+
+        engine.kill(0);
+
+        assert_eq!(engine.state(), State::GameOver);
+        assert_eq!(engine.winner(), Some(1));
+    }
+}
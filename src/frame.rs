@@ -0,0 +1,343 @@
+use std::io::{ self, Write };
+use termion::cursor;
+
+use crate::theme::{ Color, RESET_BG, RESET_FG };
+
+// Unchanged characters between two changes on the same line are reprinted
+// when they are shorter than this, as that is cheaper than moving the cursor
+const MAX_GAP: usize = 6;
+
+// A character with its foreground and background colors, `None` being the
+// terminal's default.
+type Glyph = (char, Option<Color>, Option<Color>);
+
+const BLANK: Glyph = (' ', None, None);
+
+// Text to be drawn on the terminal, one character per column.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    lines: Vec<Vec<Glyph>>,
+}
+
+impl Frame {
+    pub fn new<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            lines: lines.into_iter()
+                .map(|line| line.as_ref().chars().map(|c| (c, None, None)).collect())
+                .collect(),
+        }
+    }
+
+    pub fn from_glyphs(lines: Vec<Vec<Glyph>>) -> Self {
+        Self { lines }
+    }
+
+    // Cells past the end of a line are blank.
+    fn get(&self, x: usize, y: usize) -> Glyph {
+        self.lines.get(y)
+            .and_then(|line| line.get(x))
+            .copied()
+            .unwrap_or(BLANK)
+    }
+
+    fn line_len(&self, y: usize) -> usize {
+        self.lines.get(y).map_or(0, |line| line.len())
+    }
+}
+
+// Draws frames by printing only the characters that differ from the frame
+// drawn before.
+#[derive(Default)]
+pub struct DiffRenderer {
+    // The last frame drawn and its top left corner
+    previous: Option<(Frame, (u16, u16))>,
+    // The last frame drawn before `invalidate`, whose leftovers are blanked
+    // when the next frame is drawn in full
+    stale: Option<(Frame, (u16, u16))>,
+}
+
+impl DiffRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Makes the next frame be drawn in full, blanks included. Needed whenever
+    // something else has drawn over the frame or cleared the screen.
+    pub fn invalidate(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            self.stale = Some(previous);
+        }
+    }
+
+    // Draws `frame` with its top left corner at `origin`, given as
+    // one-based column and row.
+    pub fn draw<W: Write>(
+        &mut self,
+        out: &mut W,
+        frame: Frame,
+        origin: (u16, u16),
+    ) -> io::Result<()> {
+        let previous = match self.previous.take() {
+            Some((previous, previous_origin)) if previous_origin == origin => {
+                Some(previous)
+            },
+            Some((previous, previous_origin)) => {
+                // Blank out the old frame, then draw the new one in full
+                let blank = Frame::new(previous.lines.iter()
+                    .map(|line| " ".repeat(line.len())));
+                write_diff(out, Some(&previous), &blank, previous_origin)?;
+                None
+            },
+            None => None,
+        };
+
+        match self.stale.take() {
+            Some((stale, stale_origin)) if previous.is_none() && stale_origin == origin => {
+                // Lines are drawn in full up to where the old frame ended
+                let height = stale.lines.len().max(frame.lines.len());
+                let padded = Frame::from_glyphs((0..height)
+                    .map(|y| {
+                        (0..stale.line_len(y).max(frame.line_len(y)))
+                            .map(|x| frame.get(x, y))
+                            .collect()
+                    })
+                    .collect());
+                write_diff(out, None, &padded, origin)?;
+            },
+            Some((stale, stale_origin)) if previous.is_none() => {
+                let blank = Frame::new(stale.lines.iter()
+                    .map(|line| " ".repeat(line.len())));
+                write_diff(out, None, &blank, stale_origin)?;
+                write_diff(out, None, &frame, origin)?;
+            },
+            _ => write_diff(out, previous.as_ref(), &frame, origin)?,
+        }
+        self.previous = Some((frame, origin));
+
+        Ok(())
+    }
+}
+
+// Prints the characters of `next` that differ from `previous`, or all of them
+// if there is no previous frame.
+fn write_diff<W: Write>(
+    out: &mut W,
+    previous: Option<&Frame>,
+    next: &Frame,
+    (left, top): (u16, u16),
+) -> io::Result<()> {
+    let empty = Frame::default();
+    let mut current_fg = None;
+    let mut current_bg = None;
+    let height = previous.unwrap_or(&empty).lines.len().max(next.lines.len());
+
+    for y in 0..height {
+        let changed = match previous {
+            Some(previous) => {
+                (0..previous.line_len(y).max(next.line_len(y)))
+                    .filter(|&x| previous.get(x, y) != next.get(x, y))
+                    .collect::<Vec<_>>()
+            },
+            None => (0..next.line_len(y)).collect(),
+        };
+
+        let mut i = 0;
+        while i < changed.len() {
+            // Extend the run over small gaps of unchanged characters
+            let start = changed[i];
+            let mut end = start;
+            while i < changed.len() && changed[i] - end <= MAX_GAP {
+                end = changed[i];
+                i += 1;
+            }
+
+            let mut run = String::new();
+            for x in start..=end {
+                let (symbol, fg_color, bg_color) = next.get(x, y);
+                if fg_color != current_fg {
+                    run.push_str(&fg(fg_color));
+                    current_fg = fg_color;
+                }
+                if bg_color != current_bg {
+                    run.push_str(&bg(bg_color));
+                    current_bg = bg_color;
+                }
+                run.push(symbol);
+            }
+            let goto = cursor::Goto(left + start as u16, top + y as u16);
+            write!(out, "{}{}", goto, run)?;
+        }
+    }
+
+    if current_fg.is_some() {
+        write!(out, "{}", fg(None))?;
+    }
+    if current_bg.is_some() {
+        write!(out, "{}", bg(None))?;
+    }
+
+    Ok(())
+}
+
+fn fg(color: Option<Color>) -> String {
+    match color {
+        Some(color) => color.fg(),
+        None => RESET_FG.to_string(),
+    }
+}
+
+fn bg(color: Option<Color>) -> String {
+    match color {
+        Some(color) => color.bg(),
+        None => RESET_BG.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(renderer: &mut DiffRenderer, lines: &[&str], origin: (u16, u16)) -> String {
+        let mut out = Vec::new();
+        renderer.draw(&mut out, Frame::new(lines), origin).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_draw_when_there_is_no_previous_frame_then_draws_everything() {
+        let mut renderer = DiffRenderer::new();
+
+        assert_eq!(
+            draw(&mut renderer, &["a b", "c"], (3, 2)),
+            format!("{}a b{}c", cursor::Goto(3, 2), cursor::Goto(3, 3)),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_frame_is_unchanged_then_draws_nothing() {
+        let mut renderer = DiffRenderer::new();
+        draw(&mut renderer, &["┌─┐", "└─┘"], (1, 1));
+
+        assert_eq!(draw(&mut renderer, &["┌─┐", "└─┘"], (1, 1)), "");
+    }
+
+    #[test]
+    fn test_draw_only_changed_characters() {
+        let mut renderer = DiffRenderer::new();
+        draw(&mut renderer, &["a..............b", "................"], (1, 1));
+
+        assert_eq!(
+            draw(&mut renderer, &["x..............y", "......z........."], (1, 1)),
+            format!(
+                "{}x{}y{}z",
+                cursor::Goto(1, 1),
+                cursor::Goto(16, 1),
+                cursor::Goto(7, 2),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_changes_are_close_then_joins_them() {
+        let mut renderer = DiffRenderer::new();
+        draw(&mut renderer, &["abcdef"], (1, 1));
+
+        assert_eq!(
+            draw(&mut renderer, &["AbcdeF"], (1, 1)),
+            format!("{}AbcdeF", cursor::Goto(1, 1)),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_line_gets_shorter_then_blanks_the_rest() {
+        let mut renderer = DiffRenderer::new();
+        draw(&mut renderer, &[" Score: 10", "ab"], (1, 1));
+
+        assert_eq!(
+            draw(&mut renderer, &[" Score: 9"], (1, 1)),
+            format!("{}9 {}  ", cursor::Goto(9, 1), cursor::Goto(1, 2)),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_origin_moves_then_erases_old_frame() {
+        let mut renderer = DiffRenderer::new();
+        draw(&mut renderer, &["ab"], (1, 1));
+
+        assert_eq!(
+            draw(&mut renderer, &["ab"], (2, 3)),
+            format!("{}  {}ab", cursor::Goto(1, 1), cursor::Goto(2, 3)),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_only_color_changes_then_redraws_character() {
+        let red = Some(Color::Ansi(1));
+        let blue = Some(Color::Ansi(4));
+        let mut renderer = DiffRenderer::new();
+        let mut out = Vec::new();
+        let frame = Frame::from_glyphs(vec![vec![('a', red, None), ('b', red, None), ('c', None, None)]]);
+        renderer.draw(&mut out, frame, (1, 1)).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\x1b[31mab{}c", cursor::Goto(1, 1), RESET_FG),
+        );
+
+        let mut out = Vec::new();
+        let frame = Frame::from_glyphs(vec![vec![('a', red, None), ('b', blue, None), ('c', None, None)]]);
+        renderer.draw(&mut out, frame, (1, 1)).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\x1b[34mb{}", cursor::Goto(2, 1), RESET_FG),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_background_changes_then_sets_it_apart_from_foreground() {
+        let red = Some(Color::Ansi(1));
+        let blue = Some(Color::Ansi(4));
+        let mut renderer = DiffRenderer::new();
+        let mut out = Vec::new();
+        let frame = Frame::from_glyphs(vec![vec![('a', red, blue), ('b', red, None)]]);
+        renderer.draw(&mut out, frame, (1, 1)).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\x1b[31m\x1b[44ma{}b{}", cursor::Goto(1, 1), RESET_BG, RESET_FG),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_invalidated_then_draws_everything() {
+        let mut renderer = DiffRenderer::new();
+        draw(&mut renderer, &["ab"], (1, 1));
+        renderer.invalidate();
+
+        assert_eq!(
+            draw(&mut renderer, &["ab"], (1, 1)),
+            format!("{}ab", cursor::Goto(1, 1)),
+        );
+    }
+
+    #[test]
+    fn test_draw_when_invalidated_and_lines_get_shorter_then_blanks_the_rest() {
+        let mut renderer = DiffRenderer::new();
+        draw(&mut renderer, &[" Replay 3/9 [paused]", "ab"], (1, 1));
+        renderer.invalidate();
+
+        assert_eq!(
+            draw(&mut renderer, &[" Replay 4/9"], (1, 1)),
+            format!(
+                "{} Replay 4/9         {}  ",
+                cursor::Goto(1, 1),
+                cursor::Goto(1, 2),
+            ),
+        );
+    }
+}
@@ -1,32 +1,61 @@
-use std::io::{stdout, Stdout, Write};
+use std::io::{self, stdout, Write};
 use std::thread;
 use std::time::{Duration, Instant};
 use termion::{
-    cursor,
-    event::Key,
+    event::{ Event, Key },
+    input::Events,
     input::TermRead,
     raw::IntoRawMode,
-    raw::RawTerminal,
-    screen::AlternateScreen,
-    screen::IntoAlternateScreen,
+    AsyncReader,
 };
 
+use crate::ai::{ Hamiltonian, Pathfinder, Pilot };
+use crate::difficulty::{ Difficulty, Speed };
 use crate::direction::Direction;
-use crate::grid::{
-    Cell,
-    Grid,
-    GRID_WIDTH_IN_CHARS,
-    GRID_HEIGHT_IN_CHARS,
-};
+use crate::engine::{ Engine, State };
+use crate::grid::{ Grid, Layout, Walls, MIN_GRID_WIDTH, MIN_GRID_HEIGHT };
+use crate::level::Level;
 use crate::position::Position;
-use crate::snake::{ Snake, Status };
+use crate::renderer::{ terminal_size, Renderer, Snapshot, HEADER_HEIGHT };
+use crate::replay::Replay;
+
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+const DIFFICULTIES: [Difficulty; 4] = [
+    Difficulty::Easy,
+    Difficulty::Normal,
+    Difficulty::Hard,
+    Difficulty::Insane,
+];
+
+// Playback speeds as fractions of the recorded game speed
+const PLAYBACK_SPEEDS: [(u32, u32); 6] = [
+    (1, 4), (1, 2), (1, 1), (2, 1), (4, 1), (8, 1),
+];
+const DEFAULT_PLAYBACK_SPEED: usize = 2;
+
+// The demo behind the title screen starts over after this many ticks, in
+// case its snake goes round in circles. In attract mode, where games are
+// long, the limit is on ticks without eating.
+const DEMO_MAX_TICKS: usize = 2000;
+
+// How long the attract mode shows how a game ended before the next one
+const DEMO_RESULT_PAUSE: Duration = Duration::from_secs(3);
+
+// Sent by terminals that report focus changes, see `TerminalRenderer`
+const FOCUS_OUT_EVENT: &[u8] = b"\x1b[O";
 
-const FRAME_DURATION: Duration = Duration::from_millis(100);
+// Longest stretch of time the simulation catches up on after the loop was
+// held up, so that a long stall does not fast-forward the game
+const MAX_LAG: Duration = Duration::from_millis(250);
 
+#[derive(Debug, PartialEq)]
 pub enum GameError {
+    ClearScreen,
     FlushScreen,
     GetTerminalSize,
     HideCursor,
+    PrintText,
     SetCursorPos,
     ShowCursor,
     SwitchIntoAlternateScreen,
@@ -35,151 +64,911 @@ pub enum GameError {
     TerminalWidthTooSmall,
 }
 
-#[derive(PartialEq)]
-enum State {
-    Playing,
-    GameOver,
+pub fn fit_to_terminal(layout: Layout) -> Result<(usize, usize), GameError> {
+    let (col_count, row_count) = terminal_size()?;
+    let (width, height) = Grid::size_for_chars(
+        layout,
+        col_count as usize,
+        (row_count as usize).saturating_sub(HEADER_HEIGHT),
+    );
+
+    if height < MIN_GRID_HEIGHT {
+        return Err(GameError::TerminalHeightTooSmall);
+    }
+    if width < MIN_GRID_WIDTH {
+        return Err(GameError::TerminalWidthTooSmall);
+    }
+
+    Ok((width, height))
 }
 
-pub struct Game {
-    grid: Grid,
-    snake: Snake,
-    points: usize,
+// Which snake a key steers, and where to. With two players, the first one
+// uses WASD and the second one the keys a single player would use.
+fn turn(key: Key, players: usize) -> Option<(usize, Direction)> {
+    let second = if players > 1 { 1 } else { 0 };
+    match key {
+        Key::Left  | Key::Char('h') => Some((second, Direction::Left)),
+        Key::Down  | Key::Char('j') => Some((second, Direction::Down)),
+        Key::Up    | Key::Char('k') => Some((second, Direction::Up)),
+        Key::Right | Key::Char('l') => Some((second, Direction::Right)),
+        Key::Char('a') if players > 1 => Some((0, Direction::Left)),
+        Key::Char('s') if players > 1 => Some((0, Direction::Down)),
+        Key::Char('w') if players > 1 => Some((0, Direction::Up)),
+        Key::Char('d') if players > 1 => Some((0, Direction::Right)),
+        _ => None,
+    }
 }
 
-impl Game {
-    pub fn new() -> Self {
-        let mut grid = Grid::new(); 
-        let width = grid.width();
-        let height = grid.height();
-        let origin = Position {
-            x: width / 2,
-            y: height / 2,
-        };
-        grid[origin.y*width + origin.x] = Cell::Snake;
-        grid.gen_apple();
-        let snake = Snake::new(origin);
+// Fixed-timestep clock. The simulation advances in steps of a given duration
+// however often, and however late, the loop around it runs.
+struct Timestep {
+    last: Instant,
+    lag: Duration,
+}
+
+impl Timestep {
+    fn new(now: Instant) -> Self {
         Self {
-            grid,
-            snake,
-            points: 0,
+            last: now,
+            lag: Duration::ZERO,
         }
     }
 
-    pub fn run(&mut self) -> Result<usize, GameError> {
-        let mut stdin = termion::async_stdin().keys();
-        let stdout = match stdout().into_raw_mode() {
-            Ok(stdout) => stdout,
-            Err(_) => return Err(GameError::SwitchIntoRawMode),
-        };
-        let mut screen = match stdout.into_alternate_screen() {
-            Ok(screen) => screen,
-            Err(_) => return Err(GameError::SwitchIntoAlternateScreen),
-        };
+    // Forgets the time since the last call, e.g. while the game is paused
+    fn reset(&mut self, now: Instant) {
+        self.last = now;
+        self.lag = Duration::ZERO;
+    }
 
-        if let Err(_) = write!(screen, "{}", termion::cursor::Hide) {
-            return Err(GameError::HideCursor);
+    fn advance(&mut self, now: Instant) {
+        self.lag = (self.lag + now.duration_since(self.last)).min(MAX_LAG);
+        self.last = now;
+    }
+
+    // Consumes one step if enough time has accumulated for it
+    fn step(&mut self, duration: Duration) -> bool {
+        if self.lag < duration {
+            return false;
         }
+        self.lag -= duration;
+        true
+    }
+
+    fn until_step(&self, duration: Duration) -> Duration {
+        duration.saturating_sub(self.lag)
+    }
+}
+
+pub struct Game<R: Renderer> {
+    engine: Engine,
+    // Recorded while playing, or played back by `play_back`
+    replay: Replay,
+    // The reader keeps consuming input for as long as the program runs, so
+    // all input has to go through this one instance. It is only started
+    // once input is needed.
+    stdin: Option<Events<AsyncReader>>,
+    renderer: R,
+    // Computer players, by snake. Snakes without one are steered with the
+    // keyboard.
+    pilots: Vec<Option<Box<dyn Pilot>>>,
+}
+
+impl<R: Renderer> Game<R> {
+    pub fn new(
+        level: &Level,
+        walls: Walls,
+        seed: u64,
+        speed: Speed,
+        renderer: R,
+    ) -> Self {
+        Self {
+            engine: Engine::new(level, walls, seed),
+            replay: Replay::new(level.clone(), walls, seed, speed),
+            stdin: None,
+            renderer,
+            pilots: Vec::new(),
+        }
+    }
+
+    // A game with one snake per spawn, the first one steered with WASD and
+    // the second one with the arrow keys or hjkl. It is not recorded.
+    pub fn with_spawns(
+        level: &Level,
+        walls: Walls,
+        seed: u64,
+        speed: Speed,
+        spawns: &[(Position, Direction)],
+        renderer: R,
+    ) -> Self {
+        Self {
+            engine: Engine::with_spawns(level, walls, seed, spawns),
+            replay: Replay::new(level.clone(), walls, seed, speed),
+            stdin: None,
+            renderer,
+            pilots: Vec::new(),
+        }
+    }
+
+    pub fn from_replay(replay: Replay, renderer: R) -> Self {
+        Self {
+            engine: Engine::new(&replay.level, replay.walls, replay.seed),
+            replay,
+            stdin: None,
+            renderer,
+            pilots: Vec::new(),
+        }
+    }
+
+    // Lets `pilot` steer the snake of `player` instead of the keyboard.
+    pub fn set_pilot(&mut self, player: usize, pilot: Box<dyn Pilot>) {
+        self.pilots.resize_with(self.engine.snakes().len(), || None);
+        self.pilots[player] = Some(pilot);
+    }
+
+    // Snakes steered with the keyboard, in the order `turn` numbers them
+    fn humans(&self) -> Vec<usize> {
+        (0..self.engine.snakes().len())
+            .filter(|&player| self.pilots.get(player).is_none_or(Option::is_none))
+            .collect()
+    }
+
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.engine.seed()
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.replay.speed
+    }
 
-        let mut time = Instant::now();
+    pub fn scores(&self) -> &[usize] {
+        self.engine.scores()
+    }
+
+    pub fn winner(&self) -> Option<usize> {
+        self.engine.winner()
+    }
+
+    fn next_event(&mut self) -> Option<io::Result<Event>> {
+        self.stdin
+            .get_or_insert_with(|| termion::async_stdin().events())
+            .next()
+    }
+
+    fn tick_duration(&self) -> Duration {
+        self.speed().tick_duration(self.engine.points())
+    }
+
+    // Lets the player pick the difficulty before the game starts, while the
+    // computer plays a demo behind the menu. Returns false if the player
+    // quit instead.
+    pub fn choose_speed(&mut self) -> Result<bool, GameError> {
+        self.with_screen(Self::choose_speed_on_screen)
+    }
+
+    fn choose_speed_on_screen(&mut self) -> Result<bool, GameError> {
+        let mut size = self.renderer.size()?;
+        let mut speed = self.replay.speed;
+        let mut choice = DIFFICULTIES.iter()
+            .position(|&difficulty| difficulty == speed.difficulty)
+            .unwrap_or(1);
+
+        let level = self.replay.level.clone();
+        let walls = self.replay.walls;
+        let mut seed = self.engine.seed();
+        let mut demo = Engine::new(&level, walls, seed);
+        let mut pilot = Pathfinder::new();
+        let mut demo_ticks = 0;
+        let mut timestep = Timestep::new(Instant::now());
+
+        self.renderer.clear()?;
+        let mut redraw = true;
+        let start = loop {
+            let new_size = self.renderer.size()?;
+            if new_size != size {
+                size = new_size;
+                redraw = true;
+                self.renderer.clear()?;
+            }
+
+            let mut key = None;
+            while let Some(Ok(event)) = self.next_event() {
+                if let Event::Key(pressed) = event {
+                    key = Some(pressed);
+                    break;
+                }
+            }
+            match key {
+                Some(Key::Left) | Some(Key::Char('h')) => {
+                    choice = choice.saturating_sub(1);
+                },
+                Some(Key::Right) | Some(Key::Char('l')) => {
+                    choice = (choice + 1).min(DIFFICULTIES.len() - 1);
+                },
+                Some(Key::Char('p')) => {
+                    speed.progressive = !speed.progressive;
+                },
+                Some(Key::Char('\n')) => break true,
+                Some(Key::Char('q')) | Some(Key::Esc) | Some(Key::Ctrl('c')) => break false,
+                _ => (),
+            }
+            if key.is_some() {
+                redraw = true;
+            }
+            speed.difficulty = DIFFICULTIES[choice];
+
+            // The demo is played at the chosen speed
+            timestep.advance(Instant::now());
+            while timestep.step(speed.tick_duration(demo.points())) {
+                if demo.state() != State::Playing || demo_ticks >= DEMO_MAX_TICKS {
+                    seed = seed.wrapping_add(1);
+                    demo = Engine::new(&level, walls, seed);
+                    pilot = Pathfinder::new();
+                    demo_ticks = 0;
+                }
+                let dir = pilot.steer(&demo, 0);
+                demo.tick(&dir.into_iter().collect::<Vec<_>>());
+                demo_ticks += 1;
+                redraw = true;
+            }
+
+            if redraw {
+                draw(&mut self.renderer, &demo, speed, size, "", true)?;
+                let lines = [
+                    String::from(" SNAKE "),
+                    String::new(),
+                    format!(" Difficulty: < {:^6} > ", speed.difficulty.name()),
+                    format!(
+                        " Progressive: {:<3} ",
+                        if speed.progressive { "on" } else { "off" },
+                    ),
+                    String::new(),
+                    String::from(" Left/Right: difficulty, P: progressive "),
+                    String::from(" Enter: start, Q: quit "),
+                ];
+                self.renderer.render_message(&lines)?;
+                redraw = false;
+            }
+
+            let until_tick = timestep.until_step(speed.tick_duration(demo.points()));
+            thread::sleep(INPUT_POLL_INTERVAL.min(until_tick));
+        };
+        self.replay.speed = speed;
+        // Leftovers of the demo would stay on the screen
+        self.renderer.clear()?;
+
+        Ok(start)
+    }
+
+    pub fn run(&mut self) -> Result<usize, GameError> {
+        self.with_screen(Self::run_on_screen)
+    }
+
+    fn run_on_screen(&mut self) -> Result<usize, GameError> {
+        let mut size = self.renderer.size()?;
+
+        let players = self.engine.snakes().len();
+        let humans = self.humans();
+        let mut timestep = Timestep::new(Instant::now());
         let mut state = State::Playing;
+        let mut inputs = vec![Vec::new(); players];
+        let mut paused = false;
+        let mut redraw = true;
         while state == State::Playing {
-            state = self.update();
-            if let Err(e) = self.render(&mut screen) {
-                write!(screen, "{}", termion::cursor::Show).unwrap();
-                return Err(e);
+            let new_size = self.renderer.size()?;
+            if new_size != size {
+                // Leftovers of the previous layout would stay on the screen
+                size = new_size;
+                paused = true;
+                redraw = true;
+                self.renderer.clear()?;
+            }
+
+            let mut input = self.next_event();
+            while let Some(Ok(event)) = input {
+                let key = match event {
+                    Event::Key(key) => key,
+                    Event::Unsupported(bytes) if bytes == FOCUS_OUT_EVENT => {
+                        paused = true;
+                        redraw = true;
+                        input = self.next_event();
+                        continue;
+                    },
+                    _ => {
+                        input = self.next_event();
+                        continue;
+                    },
+                };
+                match key {
+                    Key::Char('p') | Key::Char(' ') => {
+                        paused = !paused;
+                        redraw = true;
+                        // Remove the pause message, which can stick out of
+                        // the board
+                        if !paused {
+                            self.renderer.clear()?;
+                        }
+                    },
+                    // Turns made while paused are dropped
+                    _ if paused && key != Key::Char('q') => (),
+                    Key::Char('q') => {
+                        state = State::GameOver;
+                    },
+                    _ => {
+                        if let Some((human, dir)) = turn(key, humans.len()) {
+                            inputs[humans[human]].push(dir);
+                        }
+                    },
+                }
+                input = self.next_event();
             }
 
-            let elapsed = Instant::now().duration_since(time);
-            if let Some(t) = FRAME_DURATION.checked_sub(elapsed) {
-                thread::sleep(t);
+            // The game is also suspended until the board fits the terminal.
+            // Time spent suspended does not count, so the snake moves a full
+            // tick after the game resumes.
+            if self.fits(size) && !paused {
+                timestep.advance(Instant::now());
+            } else {
+                timestep.reset(Instant::now());
+            }
+            while state == State::Playing
+                && timestep.step(self.tick_duration())
+            {
+                for (player, pilot) in self.pilots.iter_mut().enumerate() {
+                    if let Some(pilot) = pilot {
+                        if self.engine.is_alive(player) {
+                            inputs[player].extend(pilot.steer(&self.engine, player));
+                        }
+                    }
+                }
+                if players == 1 {
+                    self.replay.record(&inputs[0]);
+                }
+                let player_inputs = inputs.iter()
+                    .map(Vec::as_slice)
+                    .collect::<Vec<_>>();
+                (state, _) = self.engine.tick_players(&player_inputs);
+                inputs.iter_mut().for_each(Vec::clear);
+                redraw = true;
             }
-            time = Instant::now();
 
-            let mut input = stdin.next();
-            while let Some(Ok(key)) = input {
+            if redraw {
+                self.render(size, "", paused)?;
+                if paused && self.fits(size) {
+                    let lines = [
+                        String::from(" PAUSED "),
+                        String::from(" Press P to resume "),
+                    ];
+                    self.renderer.render_message(&lines)?;
+                }
+                redraw = false;
+            }
+
+            let until_tick = timestep.until_step(self.tick_duration());
+            thread::sleep(INPUT_POLL_INTERVAL.min(until_tick));
+        }
+
+        if players > 1 && self.engine.state() != State::Playing {
+            let result = match self.engine.winner() {
+                Some(player) => format!(" Player {} wins! ", player + 1),
+                None => String::from(" Draw! "),
+            };
+            let scores = self.engine.scores().iter()
+                .map(|points| points.to_string())
+                .collect::<Vec<_>>()
+                .join(" - ");
+            let lines = [
+                result,
+                format!(" {} ", scores),
+                String::from(" Press any key to exit "),
+            ];
+            self.renderer.render_message(&lines)?;
+            self.wait_for_key();
+        } else if state == State::Won {
+            let lines = [
+                String::from(" You won! "),
+                String::from(" Press any key to exit "),
+            ];
+            self.renderer.render_message(&lines)?;
+            self.wait_for_key();
+        }
+
+        Ok(self.engine.points())
+    }
+
+    fn wait_for_key(&mut self) {
+        // Keys pressed while steering would otherwise dismiss the message
+        // right away
+        while let Some(Ok(_)) = self.next_event() {}
+        while !matches!(self.next_event(), Some(Ok(Event::Key(_)))) {
+            thread::sleep(INPUT_POLL_INTERVAL);
+        }
+    }
+
+    // Plays the replay the game was created from. Space pauses, `n` steps
+    // one tick forward, `+` and `-` change the speed and `q` quits.
+    pub fn play_back(&mut self) -> Result<usize, GameError> {
+        self.with_screen(Self::play_back_on_screen)
+    }
+
+    fn play_back_on_screen(&mut self) -> Result<usize, GameError> {
+        let mut size = self.renderer.size()?;
+
+        let mut timestep = Timestep::new(Instant::now());
+        let mut speed = DEFAULT_PLAYBACK_SPEED;
+        let mut paused = false;
+        let mut step = false;
+        let mut tick = 0;
+        let mut state = State::Playing;
+        let mut quit = false;
+        let mut redraw = true;
+        while !quit {
+            let new_size = self.renderer.size()?;
+            if new_size != size {
+                size = new_size;
+                redraw = true;
+                self.renderer.clear()?;
+            }
+
+            let mut input = self.next_event();
+            while let Some(Ok(event)) = input {
+                let key = match event {
+                    Event::Key(key) => key,
+                    _ => {
+                        input = self.next_event();
+                        continue;
+                    },
+                };
                 match key {
-                    Key::Left  | Key::Char('h') => {
-                        self.snake.set_dir(Direction::Left);
+                    Key::Char(' ') | Key::Char('p') => {
+                        paused = !paused;
                     },
-                    Key::Down  | Key::Char('j') => {
-                        self.snake.set_dir(Direction::Down);
+                    Key::Char('n') | Key::Right => {
+                        paused = true;
+                        step = true;
                     },
-                    Key::Up    | Key::Char('k') => {
-                        self.snake.set_dir(Direction::Up);
+                    Key::Char('+') | Key::Char('=') | Key::Up => {
+                        speed = (speed + 1).min(PLAYBACK_SPEEDS.len() - 1);
                     },
-                    Key::Right | Key::Char('l') => {
-                        self.snake.set_dir(Direction::Right);
+                    Key::Char('-') | Key::Down => {
+                        speed = speed.saturating_sub(1);
                     },
                     Key::Char('q') => {
-                        state = State::GameOver;
+                        quit = true;
                     },
                     _ => (),
                 }
-                input = stdin.next();
+                redraw = true;
+                input = self.next_event();
             }
-        }
 
-        if let Err(_) = write!(screen, "{}", termion::cursor::Show) {
-            return Err(GameError::ShowCursor);
+            let (num, den) = PLAYBACK_SPEEDS[speed];
+            if self.fits(size) && !paused {
+                timestep.advance(Instant::now());
+            } else {
+                timestep.reset(Instant::now());
+            }
+            loop {
+                let finished = tick >= self.replay.ticks || state != State::Playing;
+                if finished || !self.fits(size) {
+                    break;
+                }
+                if !step && !timestep.step(self.tick_duration()*den/num) {
+                    break;
+                }
+                let inputs = self.replay.inputs_at(tick);
+                (state, _) = self.engine.tick(inputs);
+                tick += 1;
+                step = false;
+                redraw = true;
+            }
+            step = false;
+
+            if redraw {
+                let status = format!(
+                    "  Replay {}/{} {}{}",
+                    tick,
+                    self.replay.ticks,
+                    speed_label(num, den),
+                    if paused { " [paused]" } else { "" },
+                );
+                self.render(size, &status, paused)?;
+                redraw = false;
+            }
+
+            let until_tick = timestep.until_step(self.tick_duration()*den/num);
+            thread::sleep(INPUT_POLL_INTERVAL.min(until_tick));
         }
 
-        Ok(self.points)
+        Ok(self.engine.points())
+    }
+
+    // Lets the computer play one game after another on the game's board,
+    // following a cycle through every cell so that it fills the board. `+`
+    // and `-` change the speed and any other key quits.
+    pub fn attract(&mut self) -> Result<(), GameError> {
+        self.with_screen(Self::attract_on_screen)
     }
 
-    fn update(&mut self) -> State {
-        match self.snake.update(&mut self.grid) {
-            Status::Ate => {
-                self.grid.gen_apple();
-                self.points += 1;
-                State::Playing
-            },
-            Status::Died => State::GameOver,
-            Status::Moved => State::Playing,
+    fn attract_on_screen(&mut self) -> Result<(), GameError> {
+        let mut size = self.renderer.size()?;
+
+        let level = self.replay.level.clone();
+        let walls = self.replay.walls;
+        let mut pilot = Hamiltonian::new();
+        let mut timestep = Timestep::new(Instant::now());
+        let mut speed = DEFAULT_PLAYBACK_SPEED;
+        let mut games = 0;
+        let mut won = 0;
+        let mut hungry = 0;
+        // When the last game ended, and how
+        let mut ended: Option<(Instant, &str)> = None;
+        let mut redraw = true;
+        self.renderer.clear()?;
+        loop {
+            let new_size = self.renderer.size()?;
+            if new_size != size {
+                size = new_size;
+                redraw = true;
+                self.renderer.clear()?;
+            }
+
+            let mut key = None;
+            while let Some(Ok(event)) = self.next_event() {
+                if let Event::Key(pressed) = event {
+                    key = Some(pressed);
+                    break;
+                }
+            }
+            match key {
+                Some(Key::Char('+')) | Some(Key::Char('=')) | Some(Key::Up) => {
+                    speed = (speed + 1).min(PLAYBACK_SPEEDS.len() - 1);
+                    redraw = true;
+                },
+                Some(Key::Char('-')) | Some(Key::Down) => {
+                    speed = speed.saturating_sub(1);
+                    redraw = true;
+                },
+                Some(_) => break,
+                None => (),
+            }
+
+            // After a pause on the result, the next game starts
+            if ended.is_some_and(|(ended, _)| ended.elapsed() >= DEMO_RESULT_PAUSE) {
+                let seed = self.engine.seed().wrapping_add(1);
+                self.engine = Engine::new(&level, walls, seed);
+                pilot = Hamiltonian::new();
+                hungry = 0;
+                ended = None;
+                timestep.reset(Instant::now());
+                self.renderer.clear()?;
+                redraw = true;
+            }
+
+            let (num, den) = PLAYBACK_SPEEDS[speed];
+            if self.fits(size) {
+                timestep.advance(Instant::now());
+            } else {
+                timestep.reset(Instant::now());
+            }
+            // A snake following the cycle gets to the apple within a lap, so
+            // one that takes longer, left to the pathfinder on a board
+            // without a cycle, is going round in circles
+            let max_hungry = DEMO_MAX_TICKS.max(self.engine.grid().size());
+            while ended.is_none() && timestep.step(self.tick_duration()*den/num) {
+                let dir = pilot.steer(&self.engine, 0);
+                let points = self.engine.points();
+                let (state, _) = self.engine.tick(&dir.into_iter().collect::<Vec<_>>());
+                hungry = if self.engine.points() > points { 0 } else { hungry + 1 };
+                let result = match state {
+                    State::Won => Some(" Board full! "),
+                    State::GameOver => Some(" Game over "),
+                    State::Playing if hungry >= max_hungry => Some(" Game over "),
+                    State::Playing => None,
+                };
+                if let Some(result) = result {
+                    games += 1;
+                    if state == State::Won {
+                        won += 1;
+                    }
+                    ended = Some((Instant::now(), result));
+                }
+                redraw = true;
+            }
+
+            if redraw {
+                let status = format!(
+                    "  Demo {}  Won {}/{}",
+                    speed_label(num, den),
+                    won,
+                    games,
+                );
+                self.render(size, &status, ended.is_some())?;
+                if let Some((_, result)) = ended {
+                    self.renderer.render_message(&[String::from(result)])?;
+                }
+                redraw = false;
+            }
+
+            let until_tick = timestep.until_step(self.tick_duration()*den/num);
+            thread::sleep(INPUT_POLL_INTERVAL.min(until_tick));
         }
+
+        Ok(())
     }
 
-    fn render(
-        &self,
-        screen: &mut AlternateScreen<RawTerminal<Stdout>>,
-    ) -> Result<(), GameError> {
-        let (col_count, row_count) = match termion::terminal_size() {
-            Ok(size) => size,
-            Err(_) => return Err(GameError::GetTerminalSize),
+    pub fn state(&self) -> State {
+        self.engine.state()
+    }
+
+    // Reads a line typed by the player after the game has ended. Returns
+    // `None` if the player cancelled with Esc or Ctrl-C.
+    pub fn prompt(&mut self, message: &str) -> Result<Option<String>, GameError> {
+        let mut stdout = match stdout().into_raw_mode() {
+            Ok(stdout) => stdout,
+            Err(_) => return Err(GameError::SwitchIntoRawMode),
         };
+        if write!(stdout, "{}", message).is_err() {
+            return Err(GameError::PrintText);
+        }
+
+        let mut line = String::new();
+        let mut cancelled = false;
+        loop {
+            if stdout.flush().is_err() {
+                return Err(GameError::FlushScreen);
+            }
+            let key = match self.next_event() {
+                Some(Ok(Event::Key(key))) => key,
+                Some(_) => continue,
+                None => {
+                    thread::sleep(INPUT_POLL_INTERVAL);
+                    continue;
+                },
+            };
+            let echo = match key {
+                Key::Char('\n') => break,
+                Key::Esc | Key::Ctrl('c') => {
+                    cancelled = true;
+                    break;
+                },
+                Key::Backspace => {
+                    if line.pop().is_none() {
+                        continue;
+                    }
+                    String::from("\x08 \x08")
+                },
+                Key::Char(c) if !c.is_control() => {
+                    line.push(c);
+                    c.to_string()
+                },
+                _ => continue,
+            };
+            if write!(stdout, "{}", echo).is_err() {
+                return Err(GameError::PrintText);
+            }
+        }
+
+        if write!(stdout, "\r\n").is_err() {
+            return Err(GameError::PrintText);
+        }
 
-        if GRID_HEIGHT_IN_CHARS + 1 > (row_count as usize) {
+        Ok(if cancelled { None } else { Some(line) })
+    }
+
+    // Runs `f` with the screen open, closing it again even if `f` fails.
+    fn with_screen<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, GameError>,
+    ) -> Result<T, GameError> {
+        let (col_count, row_count) = self.renderer.size()?;
+        let (width, height) = self.renderer.board_size(self.engine.grid());
+        if height > row_count as usize {
             return Err(GameError::TerminalHeightTooSmall);
         }
-        if GRID_WIDTH_IN_CHARS > (col_count as usize) {
+        if width > col_count as usize {
             return Err(GameError::TerminalWidthTooSmall);
         }
 
-        let top_margin = row_count/2 - (GRID_HEIGHT_IN_CHARS as u16)/2;
-        let left_margin = (col_count as usize)/2 - GRID_WIDTH_IN_CHARS/2;
+        self.renderer.open()?;
+        let result = f(self);
+        let closed = self.renderer.close();
 
-        if let Err(_) = write!(screen, "{}", cursor::Goto(1, top_margin)) {
-            return Err(GameError::SetCursorPos);
-        }
+        let value = result?;
+        closed?;
+        Ok(value)
+    }
+
+    fn fits(&self, size: (u16, u16)) -> bool {
+        fits(&self.renderer, &self.engine, size)
+    }
+
+    fn render(
+        &mut self,
+        size: (u16, u16),
+        status: &str,
+        dimmed: bool,
+    ) -> Result<(), GameError> {
+        draw(&mut self.renderer, &self.engine, self.replay.speed, size, status, dimmed)
+    }
+}
+
+// Playback speeds read as `x2` or `x1/4`
+fn speed_label(num: u32, den: u32) -> String {
+    if den == 1 {
+        format!("x{}", num)
+    } else {
+        format!("x{}/{}", num, den)
+    }
+}
+
+fn fits<R: Renderer>(renderer: &R, engine: &Engine, (col_count, row_count): (u16, u16)) -> bool {
+    let (width, height) = renderer.board_size(engine.grid());
+    width <= col_count as usize && height <= row_count as usize
+}
+
+// Draws `engine`, or asks for a bigger terminal if its board does not fit.
+fn draw<R: Renderer>(
+    renderer: &mut R,
+    engine: &Engine,
+    speed: Speed,
+    size: (u16, u16),
+    status: &str,
+    dimmed: bool,
+) -> Result<(), GameError> {
+    if !fits(renderer, engine, size) {
+        let (width, height) = renderer.board_size(engine.grid());
+        let lines = [
+            String::from("Resize your terminal"),
+            format!("({}x{} needed)", width, height),
+        ];
+        return renderer.render_message(&lines);
+    }
+
+    let snapshot = Snapshot {
+        grid: engine.grid(),
+        snakes: engine.snakes(),
+        scores: engine.scores(),
+        speed,
+        status,
+        dimmed,
+    };
+    renderer.render(&snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::MemoryRenderer;
+
+    const STEP: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn test_turn_when_alone_then_wasd_does_not_steer() {
+        assert_eq!(turn(Key::Left, 1), Some((0, Direction::Left)));
+        assert_eq!(turn(Key::Char('k'), 1), Some((0, Direction::Up)));
+        assert_eq!(turn(Key::Char('a'), 1), None);
+    }
+
+    #[test]
+    fn test_turn_when_two_players_then_splits_the_keyboard() {
+        assert_eq!(turn(Key::Char('w'), 2), Some((0, Direction::Up)));
+        assert_eq!(turn(Key::Char('d'), 2), Some((0, Direction::Right)));
+        assert_eq!(turn(Key::Down, 2), Some((1, Direction::Down)));
+        assert_eq!(turn(Key::Char('h'), 2), Some((1, Direction::Left)));
+    }
+
+    #[test]
+    fn test_humans_when_a_pilot_plays_then_leaves_out_its_snake() {
+        let level = Level::parse("\
+....
+^...
+").unwrap();
+        let spawns = level.mirrored_spawn()
+            .map(|second| vec![(level.spawn, level.dir), second])
+            .unwrap();
+        let renderer = MemoryRenderer::new((80, 24));
+        let mut game = Game::with_spawns(
+            &level, Walls::Solid, 0, Speed::default(), &spawns, renderer,
+        );
+        assert_eq!(game.humans(), [0, 1]);
+
+        game.set_pilot(1, Box::new(Pathfinder::new()));
+
+        assert_eq!(game.humans(), [0]);
+    }
 
-        let padding = String::from(" ").repeat(left_margin);
+    #[test]
+    fn test_timestep_steps_once_per_duration() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(start);
 
-        print!("{} Score: {}\r\n", padding, self.points);
+        timestep.advance(start + Duration::from_millis(60));
+        assert!(!timestep.step(STEP));
+        assert_eq!(timestep.until_step(STEP), Duration::from_millis(40));
+
+        timestep.advance(start + Duration::from_millis(230));
+        assert!(timestep.step(STEP));
+        assert!(timestep.step(STEP));
+        assert!(!timestep.step(STEP));
+        assert_eq!(timestep.until_step(STEP), Duration::from_millis(70));
+    }
+
+    #[test]
+    fn test_timestep_when_reset_then_forgets_elapsed_time() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(start);
+
+        timestep.advance(start + Duration::from_millis(90));
+        timestep.reset(start + Duration::from_millis(500));
+        timestep.advance(start + Duration::from_millis(550));
+
+        assert!(!timestep.step(STEP));
+        assert_eq!(timestep.until_step(STEP), Duration::from_millis(50));
+    }
 
-        let output = self.grid.render()
-            .lines()
-            .map(|line|
-                format!("{}{}", padding, line)
-            )
-            .collect::<Vec<_>>()
-            .join("\r\n");
+    #[test]
+    fn test_timestep_when_stalled_then_catches_up_at_most_max_lag() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(start);
 
-        print!("{}", output);
+        timestep.advance(start + Duration::from_secs(10));
 
-        if let Err(_) = screen.flush() {
-            return Err(GameError::FlushScreen);
+        let mut steps = 0;
+        while timestep.step(STEP) {
+            steps += 1;
         }
+        assert_eq!(steps, MAX_LAG.as_millis()/STEP.as_millis());
+    }
 
-        Ok(())
+    fn new_game(size: (u16, u16)) -> Game<MemoryRenderer> {
+        let level = Level::parse("\
+A.
+^.
+").unwrap();
+        let renderer = MemoryRenderer::new(size);
+        Game::new(&level, Walls::Solid, 0, Speed::default(), renderer)
+    }
+
+    #[test]
+    fn test_render_passes_the_game_to_the_renderer() {
+        let mut game = new_game((80, 24));
+
+        game.render((80, 24), "  Replay 1/2", false).unwrap();
+
+        let header = " Score: 0  Speed: 1 (normal)  Replay 1/2";
+        let board = "\
+┌─┬─┐
+├▴┤ │
+└─┴─┘
+";
+        assert_eq!(game.renderer().frames, [format!("{}\n{}", header, board)]);
+    }
+
+    #[test]
+    fn test_render_when_board_does_not_fit_then_asks_to_resize() {
+        let mut game = new_game((4, 3));
+
+        game.render((4, 3), "", false).unwrap();
+
+        assert!(game.renderer().frames.is_empty());
+        assert_eq!(game.renderer().messages, [[
+            String::from("Resize your terminal"),
+            String::from("(5x4 needed)"),
+        ]]);
+    }
+
+    #[test]
+    fn test_run_when_terminal_is_too_small_then_fails() {
+        let mut game = new_game((5, 3));
+
+        assert_eq!(game.run().err(), Some(GameError::TerminalHeightTooSmall));
     }
 }
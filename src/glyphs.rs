@@ -0,0 +1,179 @@
+use crate::direction::Direction;
+use crate::grid::Cell;
+
+// Characters the board is drawn with.
+#[derive(Debug, PartialEq)]
+pub struct Glyphs {
+    pub name: &'static str,
+    // Indexed by the lines going up, left, right and down, from the highest
+    // bit to the lowest
+    lines: [char; 16],
+    // Horizontal and vertical edges the snake can pass through
+    dashed: (char, char),
+    // Head moving up, down, left and right
+    arrows: [char; 4],
+    // Snake, apple and wall in the compact layout
+    cells: [char; 3],
+    // Upper and lower half taken in the half-block layout
+    halves: [char; 2],
+}
+
+pub const GLYPH_SETS: [Glyphs; 5] = [
+    Glyphs {
+        name: "light",
+        lines: [
+            ' ', '╷', '╶', '┌',
+            '╴', '┐', '─', '┬',
+            '╵', '│', '└', '├',
+            '┘', '┤', '┴', '┼',
+        ],
+        dashed: ('╌', '╎'),
+        arrows: ['▴', '▾', '◂', '▸'],
+        cells: ['█', '●', '▓'],
+        halves: ['▀', '▄'],
+    },
+    Glyphs {
+        name: "heavy",
+        lines: [
+            ' ', '╻', '╺', '┏',
+            '╸', '┓', '━', '┳',
+            '╹', '┃', '┗', '┣',
+            '┛', '┫', '┻', '╋',
+        ],
+        dashed: ('╍', '╏'),
+        arrows: ['▴', '▾', '◂', '▸'],
+        cells: ['█', '●', '▓'],
+        halves: ['▀', '▄'],
+    },
+    // There are no double half lines, so lone ends are drawn whole
+    Glyphs {
+        name: "double",
+        lines: [
+            ' ', '║', '═', '╔',
+            '═', '╗', '═', '╦',
+            '║', '║', '╚', '╠',
+            '╝', '╣', '╩', '╬',
+        ],
+        dashed: ('╌', '╎'),
+        arrows: ['▴', '▾', '◂', '▸'],
+        cells: ['█', '●', '▓'],
+        halves: ['▀', '▄'],
+    },
+    Glyphs {
+        name: "rounded",
+        lines: [
+            ' ', '╷', '╶', '╭',
+            '╴', '╮', '─', '┬',
+            '╵', '│', '╰', '├',
+            '╯', '┤', '┴', '┼',
+        ],
+        dashed: ('╌', '╎'),
+        arrows: ['▴', '▾', '◂', '▸'],
+        cells: ['█', '●', '▓'],
+        halves: ['▀', '▄'],
+    },
+    // For terminals and fonts without box-drawing characters
+    Glyphs {
+        name: "ascii",
+        lines: [
+            ' ', '|', '-', '+',
+            '-', '+', '-', '+',
+            '|', '|', '+', '+',
+            '+', '+', '+', '+',
+        ],
+        dashed: ('.', ':'),
+        arrows: ['^', 'v', '<', '>'],
+        cells: ['o', '@', '#'],
+        halves: ['\'', '.'],
+    },
+];
+
+impl Glyphs {
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        GLYPH_SETS.iter().find(|glyphs| glyphs.name == name)
+    }
+
+    pub fn default_set() -> &'static Self {
+        &GLYPH_SETS[0]
+    }
+
+    pub fn line(&self, up: bool, left: bool, right: bool, down: bool) -> char {
+        let index = usize::from(up) << 3
+            | usize::from(left) << 2
+            | usize::from(right) << 1
+            | usize::from(down);
+        self.lines[index]
+    }
+
+    pub fn horizontal(&self) -> char {
+        self.line(false, true, true, false)
+    }
+
+    pub fn dashed_horizontal(&self) -> char {
+        self.dashed.0
+    }
+
+    pub fn dashed_vertical(&self) -> char {
+        self.dashed.1
+    }
+
+    pub fn cell(&self, cell: Cell) -> char {
+        match cell {
+            Cell::Empty => ' ',
+            Cell::Snake => self.cells[0],
+            Cell::Apple => self.cells[1],
+            Cell::Wall => self.cells[2],
+        }
+    }
+
+    // With both halves taken, the lower one is left to the background color.
+    pub fn halves(&self, upper: bool, lower: bool) -> char {
+        match (upper, lower) {
+            (false, false) => ' ',
+            (true, _) => self.halves[0],
+            (false, true) => self.halves[1],
+        }
+    }
+
+    pub fn arrow(&self, dir: Direction) -> char {
+        match dir {
+            Direction::Up => self.arrows[0],
+            Direction::Down => self.arrows[1],
+            Direction::Left => self.arrows[2],
+            Direction::Right => self.arrows[3],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Glyphs::from_name("heavy").map(|glyphs| glyphs.name), Some("heavy"));
+        assert_eq!(Glyphs::from_name("dotted"), None);
+    }
+
+    #[test]
+    fn test_line() {
+        let glyphs = Glyphs::default_set();
+
+        assert_eq!(glyphs.line(false, false, false, false), ' ');
+        assert_eq!(glyphs.line(true, false, false, false), '╵');
+        assert_eq!(glyphs.line(false, false, true, true), '┌');
+        assert_eq!(glyphs.line(true, true, true, true), '┼');
+        assert_eq!(glyphs.horizontal(), '─');
+    }
+
+    #[test]
+    fn test_ascii_glyphs_are_ascii() {
+        let glyphs = Glyphs::from_name("ascii").unwrap();
+
+        assert!(glyphs.lines.iter().all(char::is_ascii));
+        assert!(glyphs.arrows.iter().all(char::is_ascii));
+        assert!(glyphs.cells.iter().all(char::is_ascii));
+        assert!(glyphs.halves.iter().all(char::is_ascii));
+        assert!(glyphs.dashed.0.is_ascii() && glyphs.dashed.1.is_ascii());
+    }
+}
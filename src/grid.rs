@@ -1,55 +1,202 @@
 use std::ops::{Index, IndexMut};
 
+use rand::Rng;
+
 use crate::bitmap::{Bitmap, State};
+use crate::glyphs::Glyphs;
+use crate::position::Position;
+
+pub const DEFAULT_GRID_WIDTH: usize = 36;
+pub const DEFAULT_GRID_HEIGHT: usize = 20;
 
-const GRID_WIDTH: usize = 36;
-const GRID_HEIGHT: usize = 20;
-const GRID_SIZE: usize = GRID_WIDTH*GRID_HEIGHT;
+pub const MIN_GRID_WIDTH: usize = 2;
+pub const MIN_GRID_HEIGHT: usize = 2;
 
 const CELL_WIDTH: usize = 2;
 const CELL_HEIGHT: usize = 1;
 
-pub const GRID_WIDTH_IN_CHARS: usize = GRID_WIDTH*CELL_WIDTH + 1;
-pub const GRID_HEIGHT_IN_CHARS: usize = GRID_HEIGHT*CELL_HEIGHT + 1;
-
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Cell {
     Empty,
     Apple,
     Snake,
+    Wall,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Walls {
+    Solid,
+    Open, // The snake wraps around to the opposite edge
+}
+
+// How cells are laid out on the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    // Each cell is outlined, two characters wide
+    Outline,
+    // One character per cell
+    Compact,
+    // Two rows of cells per line, drawn with half blocks
+    HalfBlock,
+}
+
+impl Layout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "outline" => Some(Layout::Outline),
+            "compact" => Some(Layout::Compact),
+            "half-block" => Some(Layout::HalfBlock),
+            _ => None,
+        }
+    }
 }
 
 pub struct Grid {
-    cells: [Cell; GRID_SIZE],
+    cells: Vec<Cell>,
+    width: usize,
+    height: usize,
+    walls: Walls,
 }
 
 impl Grid {
-    pub fn new() -> Self {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut cells = Vec::with_capacity(width*height);
+        cells.resize_with(width*height, || Cell::Empty);
         Self {
-            cells: [const { Cell::Empty }; GRID_SIZE],
+            cells,
+            width,
+            height,
+            walls: Walls::Solid,
         }
     }
 
+    pub fn walls(&self) -> Walls {
+        self.walls
+    }
+
+    pub fn set_walls(&mut self, walls: Walls) {
+        self.walls = walls;
+    }
+
     pub fn width(&self) -> usize {
-        GRID_WIDTH
+        self.width
     }
 
     pub fn height(&self) -> usize {
-        GRID_HEIGHT
+        self.height
     }
 
     pub fn size(&self) -> usize {
-        GRID_WIDTH*GRID_HEIGHT
+        self.width*self.height
+    }
+
+    pub fn width_in_chars(&self) -> usize {
+        self.width*CELL_WIDTH + 1
+    }
+
+    pub fn height_in_chars(&self) -> usize {
+        self.height*CELL_HEIGHT + 1
+    }
+
+    // Largest number of columns whose rendering fits in `chars` characters.
+    pub fn width_for_chars(chars: usize) -> usize {
+        chars.saturating_sub(1)/CELL_WIDTH
+    }
+
+    // Largest number of rows whose rendering fits in `chars` lines.
+    pub fn height_for_chars(chars: usize) -> usize {
+        chars.saturating_sub(1)/CELL_HEIGHT
+    }
+
+    // Size of the board in `layout`, in characters.
+    pub fn size_in_chars(&self, layout: Layout) -> (usize, usize) {
+        match layout {
+            Layout::Outline => (self.width_in_chars(), self.height_in_chars()),
+            Layout::Compact => (self.width + 2, self.height + 2),
+            Layout::HalfBlock => (self.width + 2, self.height.div_ceil(2) + 2),
+        }
+    }
+
+    // Largest board whose rendering in `layout` fits in `cols` by `rows`
+    // characters.
+    pub fn size_for_chars(layout: Layout, cols: usize, rows: usize) -> (usize, usize) {
+        match layout {
+            Layout::Outline => (Self::width_for_chars(cols), Self::height_for_chars(rows)),
+            Layout::Compact => (cols.saturating_sub(2), rows.saturating_sub(2)),
+            Layout::HalfBlock => (cols.saturating_sub(2), 2*rows.saturating_sub(2)),
+        }
     }
 
     pub fn render(&self) -> String {
+        self.render_with(Glyphs::default_set())
+    }
+
+    // One character per cell inside a border.
+    pub fn render_compact(&self, glyphs: &Glyphs) -> String {
+        let mut output = self.render_edge(glyphs, true);
+        for y in 0..self.height {
+            let line = (0..self.width)
+                .map(|x| glyphs.cell(self[y*self.width + x]))
+                .collect::<String>();
+            output.push_str(&self.render_sides(glyphs, &line));
+        }
+        output.push_str(&self.render_edge(glyphs, false));
+
+        output
+    }
+
+    // Like `render_compact`, with each character holding two rows of cells,
+    // one in each half. A character with both halves taken shows the upper
+    // one, and the lower one is drawn with the background color.
+    pub fn render_half_blocks(&self, glyphs: &Glyphs) -> String {
+        let mut output = self.render_edge(glyphs, true);
+        for y in (0..self.height).step_by(2) {
+            let line = (0..self.width)
+                .map(|x| {
+                    let upper = self[y*self.width + x] != Cell::Empty;
+                    let lower = y + 1 < self.height
+                        && self[(y + 1)*self.width + x] != Cell::Empty;
+                    glyphs.halves(upper, lower)
+                })
+                .collect::<String>();
+            output.push_str(&self.render_sides(glyphs, &line));
+        }
+        output.push_str(&self.render_edge(glyphs, false));
+
+        output
+    }
+
+    // Top or bottom border of the compact layouts
+    fn render_edge(&self, glyphs: &Glyphs, top: bool) -> String {
+        let horizontal = match self.walls {
+            Walls::Solid => glyphs.horizontal(),
+            Walls::Open => glyphs.dashed_horizontal(),
+        };
+        format!(
+            "{}{}{}\n",
+            glyphs.line(!top, false, true, top),
+            horizontal.to_string().repeat(self.width),
+            glyphs.line(!top, true, false, top),
+        )
+    }
+
+    // A line of the compact layouts between the left and right borders
+    fn render_sides(&self, glyphs: &Glyphs, line: &str) -> String {
+        let vertical = match self.walls {
+            Walls::Solid => glyphs.line(true, false, false, true),
+            Walls::Open => glyphs.dashed_vertical(),
+        };
+        format!("{}{}{}\n", vertical, line, vertical)
+    }
+
+    pub fn render_with(&self, glyphs: &Glyphs) -> String {
         let width = self.width();
         let height = self.height();
         let mut output = String::new();
 
         for y in 0..=height {
             for x in 0..=width {
-                output.push_str(&self.render_cell(x, y));
+                output.push_str(&self.render_cell(x, y, glyphs));
             }
             output.pop(); // Pop the whitespace
             output.push('\n');
@@ -58,18 +205,52 @@ impl Grid {
         output
     }
 
-    pub fn gen_apple(&mut self) {
-        let mut apple_pos = rand::random::<usize>() % GRID_SIZE;
-        while self[apple_pos] == Cell::Snake {
-            apple_pos = (apple_pos + 1) % GRID_SIZE;
+    // Places an apple on a free cell chosen uniformly at random. Returns
+    // `None` if there is no free cell left.
+    pub fn gen_apple<R: Rng>(&mut self, rng: &mut R) -> Option<Position> {
+        let free_count = self.cells.iter()
+            .filter(|cell| **cell == Cell::Empty)
+            .count();
+        if free_count == 0 {
+            return None;
         }
+
+        let nth = rng.gen_range(0..free_count);
+        let apple_pos = self.cells.iter()
+            .enumerate()
+            .filter(|(_, cell)| **cell == Cell::Empty)
+            .nth(nth)
+            .map(|(i, _)| i)?;
         self[apple_pos] = Cell::Apple;
+
+        Some(Position {
+            x: apple_pos % self.width,
+            y: apple_pos / self.width,
+        })
+    }
+
+    pub fn contains(&self, cell: Cell) -> bool {
+        self.cells.contains(&cell)
     }
 
-    fn render_cell(&self, x: usize, y: usize) -> String {
+    // Indices of the cells around the corner drawn at (x, y) by `render`,
+    // where (0, 0) is the top-left corner of the board.
+    pub fn cells_at_corner(&self, x: usize, y: usize) -> Vec<usize> {
+        let mut cells = Vec::with_capacity(4);
+        for cell_y in y.saturating_sub(1)..(y + 1).min(self.height) {
+            for cell_x in x.saturating_sub(1)..(x + 1).min(self.width) {
+                cells.push(cell_y*self.width + cell_x);
+            }
+        }
+        cells
+    }
+
+    fn render_cell(&self, x: usize, y: usize, glyphs: &Glyphs) -> String {
         use State::*;
 
         let mut bitmap = Bitmap::new();
+        let mut is_border = false;
+        let mut is_occupied = false;
         let width = self.width();
         let height = self.height();
 
@@ -103,6 +284,7 @@ impl Grid {
             ]);
         } else if y == 0 || y == height {
             // Top or bottom border
+            is_border = true;
             bitmap += Bitmap::from([
                    E,
                 N, N, N,
@@ -110,6 +292,7 @@ impl Grid {
             ]);
         } else if x == 0 || x == width {
             // Left or right border
+            is_border = true;
             bitmap += Bitmap::from([
                    N,
                 E, N, E,
@@ -119,6 +302,7 @@ impl Grid {
 
         if x > 0 && y > 0 && self[(y-1)*width + (x-1)] != Cell::Empty {
             // Non-empty top-left cell
+            is_occupied = true;
             bitmap += Bitmap::from([
                    N,
                 N, N, E,
@@ -127,6 +311,7 @@ impl Grid {
         }
         if y > 0 && x < width && self[(y-1)*width + x] != Cell::Empty {
             // Non-empty top cell
+            is_occupied = true;
             bitmap += Bitmap::from([
                    N,
                 E, N, N,
@@ -135,6 +320,7 @@ impl Grid {
         }
         if x > 0 && self[y*width + (x-1)] != Cell::Empty {
             // Non-empty left cell
+            is_occupied = true;
             bitmap += Bitmap::from([
                    E,
                 N, N, E,
@@ -143,6 +329,7 @@ impl Grid {
         }
         if x < width && y < height && self[y*width + x] != Cell::Empty {
             // Non-empty cell
+            is_occupied = true;
             bitmap += Bitmap::from([
                    E,
                 E, N, N,
@@ -150,7 +337,15 @@ impl Grid {
             ]);
         }
 
-        bitmap.render()
+        if self.walls == Walls::Open && is_border && !is_occupied {
+            // Dashed lines mark the edges the snake can pass through
+            if y == 0 || y == height {
+                return glyphs.dashed_horizontal().to_string().repeat(2);
+            }
+            return format!("{} ", glyphs.dashed_vertical());
+        }
+
+        bitmap.render(glyphs)
     }
 }
 
@@ -175,10 +370,16 @@ impl IndexMut<usize> for Grid {
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq as pretty_assert_eq;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn new_grid() -> Grid {
+        Grid::new(DEFAULT_GRID_WIDTH, DEFAULT_GRID_HEIGHT)
+    }
 
     #[test]
     fn test_index() {
-        let grid = Grid::new();
+        let grid = new_grid();
         let size = grid.size();
 
         for i in 0..size {
@@ -188,16 +389,266 @@ mod tests {
 
     #[test]
     fn test_index_mut() {
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
 
         grid[2] = Cell::Apple;
 
         assert_eq!(grid[2], Cell::Apple);
     }
 
+    #[test]
+    fn test_new_with_custom_size() {
+        let grid = Grid::new(5, 3);
+
+        assert_eq!(grid.width(), 5);
+        assert_eq!(grid.height(), 3);
+        assert_eq!(grid.size(), 15);
+        assert_eq!(grid.width_in_chars(), 11);
+        assert_eq!(grid.height_in_chars(), 4);
+    }
+
+    #[test]
+    fn test_size_for_chars_is_inverse_of_size_in_chars() {
+        let grid = Grid::new(DEFAULT_GRID_WIDTH, DEFAULT_GRID_HEIGHT);
+
+        assert_eq!(Grid::width_for_chars(grid.width_in_chars()), grid.width());
+        assert_eq!(Grid::width_for_chars(grid.width_in_chars() + 1), grid.width());
+        assert_eq!(Grid::height_for_chars(grid.height_in_chars()), grid.height());
+        assert_eq!(Grid::width_for_chars(0), 0);
+    }
+
+    #[test]
+    fn test_size_for_chars_is_inverse_of_size_in_chars_in_every_layout() {
+        for layout in [Layout::Outline, Layout::Compact, Layout::HalfBlock] {
+            let grid = Grid::new(DEFAULT_GRID_WIDTH, DEFAULT_GRID_HEIGHT);
+            let (cols, rows) = grid.size_in_chars(layout);
+
+            assert_eq!(
+                Grid::size_for_chars(layout, cols, rows),
+                (grid.width(), grid.height()),
+                "{:?}",
+                layout,
+            );
+        }
+    }
+
+    #[test]
+    fn test_size_in_chars() {
+        let grid = Grid::new(5, 3);
+
+        assert_eq!(grid.size_in_chars(Layout::Outline), (11, 4));
+        assert_eq!(grid.size_in_chars(Layout::Compact), (7, 5));
+        assert_eq!(grid.size_in_chars(Layout::HalfBlock), (7, 4));
+    }
+
+    #[test]
+    fn test_render_compact() {
+        let expected = "\
+┌────┐
+│ ▓  │
+│ ██ │
+│   ●│
+└────┘
+";
+        pretty_assert_eq!(glyphs_grid().render_compact(Glyphs::default_set()), expected);
+    }
+
+    #[test]
+    fn test_render_compact_with_ascii_glyphs_when_walls_are_open() {
+        let mut grid = glyphs_grid();
+        grid.set_walls(Walls::Open);
+        let expected = "\
++....+
+: #  :
+: oo :
+:   @:
++....+
+";
+        let glyphs = Glyphs::from_name("ascii").unwrap();
+        pretty_assert_eq!(grid.render_compact(glyphs), expected);
+    }
+
+    #[test]
+    fn test_render_half_blocks() {
+        let expected = "\
+┌────┐
+│ ▀▄ │
+│   ▀│
+└────┘
+";
+        pretty_assert_eq!(glyphs_grid().render_half_blocks(Glyphs::default_set()), expected);
+    }
+
+    #[test]
+    fn test_render_half_blocks_with_ascii_glyphs() {
+        let expected = "\
++----+
+| '. |
+|   '|
++----+
+";
+        let glyphs = Glyphs::from_name("ascii").unwrap();
+        pretty_assert_eq!(glyphs_grid().render_half_blocks(glyphs), expected);
+    }
+
+    #[test]
+    fn test_cells_at_corner() {
+        let grid = Grid::new(3, 2);
+
+        assert_eq!(grid.cells_at_corner(0, 0), [0]);
+        assert_eq!(grid.cells_at_corner(1, 1), [0, 1, 3, 4]);
+        assert_eq!(grid.cells_at_corner(3, 1), [2, 5]);
+        assert_eq!(grid.cells_at_corner(3, 2), [5]);
+    }
+
+    #[test]
+    fn test_gen_apple_picks_free_cells_uniformly() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut counts = [0; 6];
+        let samples = 6000;
+
+        for _ in 0..samples {
+            let mut grid = Grid::new(3, 2);
+            grid[0] = Cell::Snake;
+            grid[1] = Cell::Snake;
+            let pos = grid.gen_apple(&mut rng).unwrap();
+            counts[pos.y*3 + pos.x] += 1;
+        }
+
+        assert_eq!(counts[0] + counts[1], 0);
+        for count in &counts[2..] {
+            // Each of the four free cells is expected 1500 times
+            assert!((1300..1700).contains(count), "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_gen_apple_when_grid_is_full_then_returns_none() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut grid = Grid::new(2, 2);
+        for i in 0..4 {
+            grid[i] = Cell::Snake;
+        }
+
+        assert_eq!(grid.gen_apple(&mut rng), None);
+    }
+
+    #[test]
+    fn test_render_when_grid_is_small() {
+        let mut grid = Grid::new(4, 2);
+        grid[7] = Cell::Snake;
+        let expected = "\
+┌───────┐
+│     ┌─┤
+└─────┴─┘
+";
+        pretty_assert_eq!(grid.render(), expected);
+    }
+
+    #[test]
+    fn test_render_when_snake_touches_a_wall() {
+        let mut grid = Grid::new(4, 3);
+        grid[5] = Cell::Wall;
+        grid[6] = Cell::Wall;
+        grid[7] = Cell::Snake;
+        let expected = "\
+┌───────┐
+│ ┌─┬─┬─┤
+│ └─┴─┴─┤
+└───────┘
+";
+        pretty_assert_eq!(grid.render(), expected);
+    }
+
+    #[test]
+    fn test_render_when_walls_are_open() {
+        let mut grid = Grid::new(4, 3);
+        grid.set_walls(Walls::Open);
+        grid[0] = Cell::Snake;
+        grid[11] = Cell::Apple;
+        let expected = "\
+┌─┬─╌╌╌╌┐
+├─┘     ╎
+╎     ┌─┤
+└─╌╌╌╌┴─┘
+";
+        pretty_assert_eq!(grid.render(), expected);
+    }
+
+    fn glyphs_grid() -> Grid {
+        let mut grid = Grid::new(4, 3);
+        grid[1] = Cell::Wall;
+        grid[5] = Cell::Snake;
+        grid[6] = Cell::Snake;
+        grid[11] = Cell::Apple;
+        grid
+    }
+
+    #[test]
+    fn test_render_with_heavy_glyphs() {
+        let expected = "\
+┏━┳━┳━━━┓
+┃ ┣━╋━┓ ┃
+┃ ┗━┻━╋━┫
+┗━━━━━┻━┛
+";
+        let glyphs = Glyphs::from_name("heavy").unwrap();
+        pretty_assert_eq!(glyphs_grid().render_with(glyphs), expected);
+    }
+
+    #[test]
+    fn test_render_with_double_glyphs() {
+        let expected = "\
+╔═╦═╦═══╗
+║ ╠═╬═╗ ║
+║ ╚═╩═╬═╣
+╚═════╩═╝
+";
+        let glyphs = Glyphs::from_name("double").unwrap();
+        pretty_assert_eq!(glyphs_grid().render_with(glyphs), expected);
+    }
+
+    #[test]
+    fn test_render_with_rounded_glyphs() {
+        let expected = "\
+╭─┬─┬───╮
+│ ├─┼─╮ │
+│ ╰─┴─┼─┤
+╰─────┴─╯
+";
+        let glyphs = Glyphs::from_name("rounded").unwrap();
+        pretty_assert_eq!(glyphs_grid().render_with(glyphs), expected);
+    }
+
+    #[test]
+    fn test_render_with_ascii_glyphs() {
+        let expected = "\
++-+-+---+
+| +-+-+ |
+| +-+-+-+
++-----+-+
+";
+        let glyphs = Glyphs::from_name("ascii").unwrap();
+        pretty_assert_eq!(glyphs_grid().render_with(glyphs), expected);
+    }
+
+    #[test]
+    fn test_render_with_ascii_glyphs_when_walls_are_open() {
+        let mut grid = glyphs_grid();
+        grid.set_walls(Walls::Open);
+        let expected = "\
++-+-+-..+
+: +-+-+ :
+: +-+-+-+
++-....+-+
+";
+        let glyphs = Glyphs::from_name("ascii").unwrap();
+        pretty_assert_eq!(grid.render_with(glyphs), expected);
+    }
+
     #[test]
     fn test_render_when_all_cells_are_empty() {
-        let grid = Grid::new();
+        let grid = new_grid();
         let expected = "\
 ┌───────────────────────────────────────────────────────────────────────┐
 │                                                                       │
@@ -226,7 +677,7 @@ mod tests {
 
     #[test]
     fn test_render_when_there_is_an_apple_cell() {
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let width = grid.width();
         let height = grid.height();
         grid[(height/2 - 1)*width + width/2 - 1] = Cell::Apple;
@@ -258,7 +709,7 @@ mod tests {
 
     #[test]
     fn test_render_when_there_is_a_snake() {
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let snake_segment_pos = vec![
             74, 75, 76, 112, 148, 184, 220, 221, 222, 223,
         ];
@@ -293,7 +744,7 @@ mod tests {
 
     #[test]
     fn test_render_when_snake_collides_with_walls() {
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let snake_segment_pos = vec![
             72, 73, 74, 38, 2, 3, 4, 40, 76, 112, 148, 184, 220, 221, 222,
             223, 224, 225, 226, 227, 228, 264, 300, 336, 372, 408, 444, 480,
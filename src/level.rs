@@ -0,0 +1,340 @@
+use std::fs;
+use std::path::Path;
+
+use crate::direction::Direction;
+use crate::grid::{ Cell, MIN_GRID_WIDTH, MIN_GRID_HEIGHT };
+use crate::position::Position;
+
+// Level file format, one character per cell:
+//
+//   #        wall
+//   .        empty cell
+//   A        apple
+//   S or ^   snake spawn, heading up
+//   v < >    snake spawn, heading down, left or right
+//
+// All rows must have the same length and there must be exactly one spawn.
+
+#[derive(Debug, PartialEq)]
+pub enum LevelError {
+    ReadFile,
+    TooSmall,
+    UnevenRows { line: usize },
+    UnknownCell { line: usize, column: usize, symbol: char },
+    MissingSpawn,
+    MultipleSpawns { line: usize, column: usize },
+}
+
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<Cell>,
+    pub spawn: Position,
+    pub dir: Direction,
+}
+
+impl Level {
+    pub fn empty(width: usize, height: usize) -> Self {
+        let mut cells = Vec::with_capacity(width*height);
+        cells.resize_with(width*height, || Cell::Empty);
+        Self {
+            width,
+            height,
+            cells,
+            spawn: Position {
+                x: width / 2,
+                y: height / 2,
+            },
+            dir: Direction::Up,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(_) => Err(LevelError::ReadFile),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LevelError> {
+        let rows = text.trim_end().lines().collect::<Vec<_>>();
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+
+        if width < MIN_GRID_WIDTH || height < MIN_GRID_HEIGHT {
+            return Err(LevelError::TooSmall);
+        }
+
+        let mut cells = Vec::with_capacity(width*height);
+        let mut spawn = None;
+
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(LevelError::UnevenRows { line: y + 1 });
+            }
+
+            for (x, symbol) in row.chars().enumerate() {
+                let cell = match symbol {
+                    '#' => Cell::Wall,
+                    '.' => Cell::Empty,
+                    'A' => Cell::Apple,
+                    'S' | '^' | 'v' | '<' | '>' => {
+                        if spawn.is_some() {
+                            return Err(LevelError::MultipleSpawns {
+                                line: y + 1,
+                                column: x + 1,
+                            });
+                        }
+                        let dir = match symbol {
+                            'v' => Direction::Down,
+                            '<' => Direction::Left,
+                            '>' => Direction::Right,
+                            _ => Direction::Up,
+                        };
+                        spawn = Some((Position { x, y }, dir));
+                        Cell::Empty
+                    },
+                    _ => {
+                        return Err(LevelError::UnknownCell {
+                            line: y + 1,
+                            column: x + 1,
+                            symbol,
+                        });
+                    },
+                };
+                cells.push(cell);
+            }
+        }
+
+        let (spawn, dir) = match spawn {
+            Some(spawn) => spawn,
+            None => return Err(LevelError::MissingSpawn),
+        };
+
+        Ok(Self {
+            width,
+            height,
+            cells,
+            spawn,
+            dir,
+        })
+    }
+
+    // Spawn for a second snake, mirroring the level's own across the middle
+    // column. `None` if that cell is taken.
+    pub fn mirrored_spawn(&self) -> Option<(Position, Direction)> {
+        let position = Position {
+            x: self.width - 1 - self.spawn.x,
+            y: self.spawn.y,
+        };
+        let dir = match self.dir {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            dir => dir,
+        };
+        if position == self.spawn
+            || self.cells[position.y*self.width + position.x] != Cell::Empty
+        {
+            return None;
+        }
+
+        Some((position, dir))
+    }
+
+    // Spawns for `count` snakes spread evenly along the row of the level's
+    // own, all heading the same way. `None` if any of those cells is taken.
+    pub fn spread_spawns(&self, count: usize) -> Option<Vec<(Position, Direction)>> {
+        if count > self.width {
+            return None;
+        }
+
+        let spawns = (0..count)
+            .map(|i| {
+                let x = (2*i + 1)*self.width/(2*count);
+                (Position { x, y: self.spawn.y }, self.dir)
+            })
+            .collect::<Vec<_>>();
+        let taken = spawns.iter()
+            .any(|(position, _)| self.cells[position.y*self.width + position.x] != Cell::Empty);
+        if taken {
+            return None;
+        }
+
+        Some(spawns)
+    }
+
+    // Inverse of `parse`.
+    pub fn serialize(&self) -> String {
+        let mut output = String::with_capacity((self.width + 1)*self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let symbol = if self.spawn == (Position { x, y }) {
+                    match self.dir {
+                        Direction::Up => '^',
+                        Direction::Down => 'v',
+                        Direction::Left => '<',
+                        Direction::Right => '>',
+                    }
+                } else {
+                    match self.cells[y*self.width + x] {
+                        Cell::Wall => '#',
+                        Cell::Apple => 'A',
+                        _ => '.',
+                    }
+                };
+                output.push(symbol);
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let level = Level::parse("\
+#####
+#.A.#
+#.>.#
+#####
+").unwrap();
+
+        assert_eq!(level.width, 5);
+        assert_eq!(level.height, 4);
+        assert_eq!(level.spawn, Position { x: 2, y: 2 });
+        assert_eq!(level.dir, Direction::Right);
+        assert_eq!(level.cells[0], Cell::Wall);
+        assert_eq!(level.cells[6], Cell::Empty);
+        assert_eq!(level.cells[7], Cell::Apple);
+        assert_eq!(level.cells[12], Cell::Empty);
+    }
+
+    #[test]
+    fn test_serialize_is_inverse_of_parse() {
+        let text = "\
+#####
+#.A.#
+#.<.#
+#####
+";
+
+        assert_eq!(Level::parse(text).unwrap().serialize(), text);
+        assert_eq!(Level::empty(3, 2).serialize(), "...\n.^.\n");
+    }
+
+    #[test]
+    fn test_mirrored_spawn() {
+        let level = Level::parse("\
+.>..#
+.....
+").unwrap();
+
+        assert_eq!(
+            level.mirrored_spawn(),
+            Some((Position { x: 3, y: 0 }, Direction::Left)),
+        );
+    }
+
+    #[test]
+    fn test_mirrored_spawn_when_cell_is_taken_then_returns_none() {
+        let walled = Level::parse("\
+.^.#.
+.....
+").unwrap();
+        let centered = Level::parse("\
+..^..
+.....
+").unwrap();
+
+        assert_eq!(walled.mirrored_spawn(), None);
+        assert_eq!(centered.mirrored_spawn(), None);
+    }
+
+    #[test]
+    fn test_spread_spawns() {
+        let level = Level::parse("\
+.........
+....v....
+").unwrap();
+
+        assert_eq!(
+            level.spread_spawns(3),
+            Some(vec![
+                (Position { x: 1, y: 1 }, Direction::Down),
+                (Position { x: 4, y: 1 }, Direction::Down),
+                (Position { x: 7, y: 1 }, Direction::Down),
+            ]),
+        );
+        assert_eq!(level.spread_spawns(10), None);
+    }
+
+    #[test]
+    fn test_spread_spawns_when_cell_is_taken_then_returns_none() {
+        let level = Level::parse("\
+......
+.#.^..
+").unwrap();
+
+        assert_eq!(level.spread_spawns(3), None);
+    }
+
+    #[test]
+    fn test_parse_when_rows_are_uneven_then_fails() {
+        let result = Level::parse("\
+....
+.S.
+....
+");
+
+        assert_eq!(result.unwrap_err(), LevelError::UnevenRows { line: 2 });
+    }
+
+    #[test]
+    fn test_parse_when_cell_is_unknown_then_fails() {
+        let result = Level::parse("\
+.S.
+.x.
+");
+
+        assert_eq!(
+            result.unwrap_err(),
+            LevelError::UnknownCell { line: 2, column: 2, symbol: 'x' },
+        );
+    }
+
+    #[test]
+    fn test_parse_when_there_is_no_spawn_then_fails() {
+        let result = Level::parse("\
+...
+...
+");
+
+        assert_eq!(result.unwrap_err(), LevelError::MissingSpawn);
+    }
+
+    #[test]
+    fn test_parse_when_there_are_two_spawns_then_fails() {
+        let result = Level::parse("\
+.S.
+.^.
+");
+
+        assert_eq!(
+            result.unwrap_err(),
+            LevelError::MultipleSpawns { line: 2, column: 2 },
+        );
+    }
+
+    #[test]
+    fn test_parse_when_level_is_too_small_then_fails() {
+        assert_eq!(Level::parse("S.\n").unwrap_err(), LevelError::TooSmall);
+        assert_eq!(Level::parse("").unwrap_err(), LevelError::TooSmall);
+    }
+}
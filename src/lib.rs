@@ -0,0 +1,20 @@
+pub mod ai;
+mod bitmap;
+pub mod difficulty;
+pub mod direction;
+pub mod engine;
+pub mod frame;
+pub mod glyphs;
+pub mod grid;
+pub mod level;
+pub mod net;
+pub mod paths;
+pub mod position;
+pub mod renderer;
+pub mod replay;
+pub mod scores;
+pub mod snake;
+pub mod spectators;
+pub mod theme;
+pub mod game;
+pub mod options;
@@ -1,49 +1,511 @@
-mod bitmap;
-mod direction;
-mod grid;
-mod position;
-mod snake;
-mod game;
+use std::env;
+use std::path::{ Path, PathBuf };
+use std::process;
+use std::time::{ SystemTime, UNIX_EPOCH };
 
-use crate::game::{ Game, GameError };
+use snake::ai::Pathfinder;
+use snake::difficulty::{ Difficulty, Speed };
+use snake::engine::State;
+use snake::game::{ self, Game, GameError };
+use snake::glyphs::Glyphs;
+use snake::direction::Direction;
+use snake::level::{ Level, LevelError };
+use snake::net::{ Client, NetError, Server, DEFAULT_PORT, MAX_GRID_CELLS };
+use snake::options::{ Command, Options, OptionsError };
+use snake::paths;
+use snake::position::Position;
+use snake::grid::{ Layout, Walls };
+use snake::renderer::{ AsciiRenderer, Renderer, TerminalRenderer };
+use snake::replay::{ Replay, ReplayError };
+use snake::scores::{ Category, Entry, Scores };
+use snake::spectators::{ SpectatedRenderer, Spectators };
+use snake::theme::Theme;
 
 fn main() {
-    let mut game = Game::new();
-    match game.run() {
-        Ok(points) => {
-            println!("Score: {}", points);
-        },
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
         Err(e) => {
             let msg = match e {
-                GameError::FlushScreen => {
-                    "Failed to flush screen"
-                },
-                GameError::GetTerminalSize => {
-                    "Failed to get terminal size"
-                }
-                GameError::HideCursor => {
-                    "Failed to hide cursor"
+                OptionsError::InvalidValue(flag) => {
+                    format!("Invalid value for {}", flag)
                 },
-                GameError::SetCursorPos => {
-                    "Failed to set cursor position"
+                OptionsError::MissingValue(flag) => {
+                    format!("Missing value for {}", flag)
                 },
-                GameError::ShowCursor => {
-                    "Failed to show cursor"
-                },
-                GameError::SwitchIntoAlternateScreen => {
-                    "Failed to switch into alternate screen"
-                },
-                GameError::SwitchIntoRawMode => {
-                    "Failed to switch into raw mode"
-                },
-                GameError::TerminalWidthTooSmall => {
-                    "Terminal width is too small"
-                },
-                GameError::TerminalHeightTooSmall => {
-                    "Terminal height is too small"
+                OptionsError::UnknownArgument(arg) => {
+                    format!("Unknown argument: {}", arg)
                 },
             };
             eprintln!("{}", msg);
+            process::exit(2);
+        }
+    };
+
+    match &options.command {
+        Command::Play => play(&options),
+        Command::Replay(path) => play_back(path, &options),
+        Command::Scores => show_scores(),
+        Command::Serve => serve(&options),
+        Command::Join(addr) => join(addr, &options),
+        Command::Demo => demo(&options),
+    }
+}
+
+// Terminals that call themselves dumb get the plain renderer too
+fn renderer(options: &Options) -> Box<dyn Renderer> {
+    let dumb = env::var("TERM").is_ok_and(|term| term == "dumb");
+    let renderer: Box<dyn Renderer> = if options.plain || dumb {
+        Box::new(AsciiRenderer::new())
+    } else {
+        let glyphs = options.glyphs.unwrap_or_else(Glyphs::default_set);
+        let theme = theme(options);
+        Box::new(TerminalRenderer::new(theme, glyphs, layout(options, theme)))
+    };
+
+    match spectators(options) {
+        Some(spectators) => Box::new(SpectatedRenderer::new(renderer, spectators)),
+        None => renderer,
+    }
+}
+
+// An explicit theme wins over NO_COLOR, see https://no-color.org
+fn theme(options: &Options) -> &'static Theme {
+    options.theme.unwrap_or_else(|| {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        if no_color {
+            Theme::mono()
+        } else {
+            Theme::default_theme()
+        }
+    })
+}
+
+// Half blocks tell the parts of the board apart by their colors alone, so
+// without colors the compact layout is used instead
+fn layout(options: &Options, theme: &Theme) -> Layout {
+    if options.layout == Layout::HalfBlock && !theme.has_colors() {
+        Layout::Compact
+    } else {
+        options.layout
+    }
+}
+
+// Spectators always get colors and box-drawing characters unless asked
+// otherwise, as their terminals are not the player's.
+fn spectators(options: &Options) -> Option<Spectators> {
+    let port = options.spectate?;
+    let theme = options.theme.unwrap_or_else(Theme::default_theme);
+    let glyphs = options.glyphs.unwrap_or_else(Glyphs::default_set);
+    match Spectators::bind(port, theme, glyphs, layout(options, theme)) {
+        Ok(spectators) => Some(spectators),
+        Err(e) => {
+            eprintln!("Port {}: {}", port, net_error_message(e));
+            process::exit(1);
+        }
+    }
+}
+
+fn load_level(options: &Options) -> Level {
+    if let Some(path) = &options.level {
+        match Level::load(path) {
+            Ok(level) => level,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), level_error_message(e));
+                process::exit(1);
+            }
+        }
+    } else if options.fit {
+        match game::fit_to_terminal(layout(options, theme(options))) {
+            Ok((width, height)) => Level::empty(width, height),
+            Err(e) => {
+                eprintln!("{}", error_message(e));
+                process::exit(1);
+            }
+        }
+    } else {
+        Level::empty(options.width, options.height)
+    }
+}
+
+// Where each of `count` snakes starts. Two snakes face each other across
+// the middle of the board, more are lined up along the level's spawn row.
+fn spawns(options: &Options, level: &mut Level, count: usize) -> Vec<(Position, Direction)> {
+    let spawns = if count == 2 {
+        if options.level.is_none() {
+            // Leave the snakes some room between them
+            level.spawn.x = level.width/4;
+        }
+        level.mirrored_spawn()
+            .map(|second| vec![(level.spawn, level.dir), second])
+    } else {
+        level.spread_spawns(count)
+    };
+
+    match spawns {
+        Some(spawns) => spawns,
+        None => {
+            eprintln!("Level has no room for {} snakes", count);
+            process::exit(1);
+        }
+    }
+}
+
+fn speed(options: &Options) -> Speed {
+    Speed {
+        difficulty: options.difficulty.unwrap_or(Difficulty::Normal),
+        progressive: options.progressive,
+    }
+}
+
+fn play(options: &Options) {
+    let mut level = load_level(options);
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let speed = speed(options);
+    let versus = options.two_player || options.cpu;
+    let mut game = if versus {
+        let spawns = spawns(options, &mut level, 2);
+        Game::with_spawns(&level, options.walls, seed, speed, &spawns, renderer(options))
+    } else {
+        Game::new(&level, options.walls, seed, speed, renderer(options))
+    };
+    if options.cpu {
+        game.set_pilot(1, Box::new(Pathfinder::new()));
+    }
+    if options.difficulty.is_none() {
+        match game.choose_speed() {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(e) => {
+                eprintln!("{}", error_message(e));
+                process::exit(1);
+            }
+        }
+    }
+    match game.run() {
+        Ok(_) if versus => {
+            print_round_result(game.scores(), game.state(), game.winner());
+        },
+        Ok(points) => {
+            if game.state() == State::Won {
+                println!("You won!");
+            }
+            println!("Score: {}", points);
+            println!("Seed: {}", game.seed());
+            save_replay(game.replay(), options.record.clone());
+            let category = score_category(options, &level, game.speed());
+            record_score(&mut game, &category, points);
+        },
+        Err(e) => {
+            eprintln!("{}", error_message(e));
+        }
+    }
+}
+
+fn demo(options: &Options) {
+    let level = load_level(options);
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let mut game = Game::new(&level, options.walls, seed, speed(options), renderer(options));
+    if let Err(e) = game.attract() {
+        eprintln!("{}", error_message(e));
+        process::exit(1);
+    }
+}
+
+fn print_round_result(scores: &[usize], state: State, winner: Option<usize>) {
+    for (i, points) in scores.iter().enumerate() {
+        println!("Player {}: {}", i + 1, points);
+    }
+    // Nobody wins a round the players quit
+    if state == State::Playing {
+        return;
+    }
+    match winner {
+        Some(player) => println!("Player {} wins!", player + 1),
+        None => println!("Draw!"),
+    }
+}
+
+fn serve(options: &Options) {
+    let mut level = load_level(options);
+    if level.width*level.height > MAX_GRID_CELLS {
+        eprintln!("Board is too big to serve, the limit is {} cells", MAX_GRID_CELLS);
+        process::exit(1);
+    }
+    let spawns = spawns(options, &mut level, options.players);
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let listener = match Server::bind(options.port) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("{}", net_error_message(e));
+            process::exit(1);
+        }
+    };
+
+    let mut server = Server::new(&level, options.walls, seed, speed(options), &spawns);
+    if let Some(spectators) = spectators(options) {
+        server.set_spectators(spectators);
+    }
+    let mut humans = options.players;
+    if options.cpu {
+        humans -= 1;
+        server.set_pilot(humans, Box::new(Pathfinder::new()));
+    }
+    let port = listener.local_addr().map_or(options.port, |addr| addr.port());
+    let noun = if humans == 1 { "player" } else { "players" };
+    println!("Waiting for {} {} on port {}", humans, noun, port);
+    while !server.is_full() {
+        match server.accept(&listener) {
+            Ok(player) => println!("Player {} joined", player + 1),
+            Err(e) => eprintln!("{}", net_error_message(e)),
+        }
+    }
+    server.run();
+    print_round_result(server.scores(), server.state(), server.winner());
+}
+
+fn join(addr: &str, options: &Options) {
+    let addr = if addr.contains(':') {
+        addr.to_string()
+    } else {
+        format!("{}:{}", addr, DEFAULT_PORT)
+    };
+    let mut client = match Client::connect(&addr, renderer(options)) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}: {}", addr, net_error_message(e));
+            process::exit(1);
+        }
+    };
+
+    match client.run() {
+        Ok(_) => {
+            if let Some(update) = client.update() {
+                print_round_result(&update.scores, update.state, update.winner);
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", net_error_message(e));
+        }
+    }
+}
+
+fn play_back(path: &Path, options: &Options) {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), replay_error_message(e));
+            process::exit(1);
+        }
+    };
+
+    let mut game = Game::from_replay(replay, renderer(options));
+    match game.play_back() {
+        Ok(points) => {
+            println!("Score: {}", points);
+        },
+        Err(e) => {
+            eprintln!("{}", error_message(e));
+        }
+    }
+}
+
+fn scores_path() -> Option<PathBuf> {
+    paths::data_dir().map(|dir| dir.join("scores.txt"))
+}
+
+fn score_category(options: &Options, level: &Level, speed: Speed) -> Category {
+    let walls = match options.walls {
+        Walls::Solid => "classic",
+        Walls::Open => "wrap",
+    };
+    let mut mode = format!("{}/{}", walls, speed.difficulty.name());
+    if speed.progressive {
+        mode.push_str("/progressive");
+    }
+    if let Some(name) = options.level.as_ref().and_then(|path| path.file_stem()) {
+        mode = format!("{}:{}", mode, name.to_string_lossy());
+    }
+
+    Category {
+        mode,
+        width: level.width,
+        height: level.height,
+    }
+}
+
+fn record_score<R: Renderer>(game: &mut Game<R>, category: &Category, points: usize) {
+    let path = match scores_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let mut scores = Scores::load(&path);
+    if !scores.qualifies(category, points) {
+        return;
+    }
+
+    let name = match game.prompt("New high score! Enter your name: ") {
+        Ok(Some(name)) if !name.trim().is_empty() => name,
+        Ok(Some(_)) => env::var("USER").unwrap_or_default(),
+        // The player would rather not be on the table
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("{}", error_message(e));
+            return;
+        }
+    };
+
+    let rank = scores.add(category, Entry { name, points });
+    if scores.save(&path).is_err() {
+        eprintln!("{}: Failed to save high scores", path.display());
+    }
+    print_scores(&scores, category, rank);
+}
+
+fn show_scores() {
+    let scores = match scores_path() {
+        Some(path) => Scores::load(&path),
+        None => Scores::default(),
+    };
+
+    let mut categories = scores.categories().peekable();
+    if categories.peek().is_none() {
+        println!("No high scores yet");
+    }
+    for (i, category) in categories.enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_scores(&scores, category, None);
+    }
+}
+
+fn print_scores(scores: &Scores, category: &Category, highlight: Option<usize>) {
+    println!("{}", category);
+    for (i, entry) in scores.top(category).iter().enumerate() {
+        let marker = if highlight == Some(i) { ">" } else { " " };
+        println!("{}{:>3}. {:<16} {:>6}", marker, i + 1, entry.name, entry.points);
+    }
+}
+
+fn save_replay(replay: &Replay, path: Option<PathBuf>) {
+    let path = path.or_else(|| {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let name = format!("{}-{}.replay", secs, replay.seed);
+        paths::data_dir().map(|dir| dir.join("replays").join(name))
+    });
+
+    if let Some(path) = path {
+        match replay.save(&path) {
+            Ok(_) => println!("Replay: {}", path.display()),
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), replay_error_message(e));
+            },
         }
     }
 }
+
+fn error_message(e: GameError) -> &'static str {
+    match e {
+        GameError::ClearScreen => {
+            "Failed to clear screen"
+        },
+        GameError::FlushScreen => {
+            "Failed to flush screen"
+        },
+        GameError::GetTerminalSize => {
+            "Failed to get terminal size"
+        }
+        GameError::HideCursor => {
+            "Failed to hide cursor"
+        },
+        GameError::PrintText => {
+            "Failed to print text"
+        },
+        GameError::SetCursorPos => {
+            "Failed to set cursor position"
+        },
+        GameError::ShowCursor => {
+            "Failed to show cursor"
+        },
+        GameError::SwitchIntoAlternateScreen => {
+            "Failed to switch into alternate screen"
+        },
+        GameError::SwitchIntoRawMode => {
+            "Failed to switch into raw mode"
+        },
+        GameError::TerminalWidthTooSmall => {
+            "Terminal width is too small"
+        },
+        GameError::TerminalHeightTooSmall => {
+            "Terminal height is too small"
+        },
+    }
+}
+
+fn net_error_message(e: NetError) -> String {
+    match e {
+        NetError::Bind => {
+            String::from("Failed to listen for connections")
+        },
+        NetError::Connect => {
+            String::from("Failed to connect")
+        },
+        NetError::Disconnected => {
+            String::from("Connection lost")
+        },
+        NetError::UnsupportedVersion => {
+            String::from("Unsupported protocol version")
+        },
+        NetError::Malformed => {
+            String::from("Malformed message")
+        },
+        NetError::Game(e) => {
+            String::from(error_message(e))
+        },
+    }
+}
+
+fn level_error_message(e: LevelError) -> String {
+    match e {
+        LevelError::ReadFile => {
+            String::from("Failed to read level file")
+        },
+        LevelError::TooSmall => {
+            String::from("Level is too small")
+        },
+        LevelError::UnevenRows { line } => {
+            format!("Line {} has a different length than the first one", line)
+        },
+        LevelError::UnknownCell { line, column, symbol } => {
+            format!("Unknown cell '{}' at line {}, column {}", symbol, line, column)
+        },
+        LevelError::MissingSpawn => {
+            String::from("Level has no snake spawn")
+        },
+        LevelError::MultipleSpawns { line, column } => {
+            format!("Second snake spawn at line {}, column {}", line, column)
+        },
+    }
+}
+
+fn replay_error_message(e: ReplayError) -> String {
+    match e {
+        ReplayError::ReadFile => {
+            String::from("Failed to read replay file")
+        },
+        ReplayError::WriteFile => {
+            String::from("Failed to write replay file")
+        },
+        ReplayError::UnsupportedVersion => {
+            String::from("Unsupported replay version")
+        },
+        ReplayError::Malformed { line } => {
+            format!("Malformed replay at line {}", line)
+        },
+        ReplayError::Level(e) => {
+            format!("Invalid level in replay: {}", level_error_message(e))
+        },
+    }
+}
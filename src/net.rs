@@ -0,0 +1,1050 @@
+use std::io::{ self, BufRead, BufReader, Read, Write };
+use std::net::{ Shutdown, TcpListener, TcpStream };
+use std::sync::mpsc::{ self, Receiver, Sender, TryRecvError };
+use std::thread;
+use std::time::{ Duration, Instant };
+use termion::{
+    event::{ Event, Key },
+    input::Events,
+    input::TermRead,
+    AsyncReader,
+};
+
+use crate::ai::Pilot;
+use crate::difficulty::{ Difficulty, Speed };
+use crate::direction::Direction;
+use crate::engine::{ Engine, State };
+use crate::game::GameError;
+use crate::grid::{ Cell, Grid, Walls, MIN_GRID_WIDTH };
+use crate::level::Level;
+use crate::position::Position;
+use crate::renderer::{ Renderer, Snapshot };
+use crate::snake::Snake;
+use crate::spectators::Spectators;
+
+// Wire protocol, one message per line. Both sides first send their
+// version, and each side hangs up on a version it does not speak:
+//
+//   server: snake-net 1
+//   client: snake-net 1
+//   server: welcome 2 3 normal off
+//
+// The welcome tells the client which player it is (from 1), how many
+// players there are, the difficulty and whether the game speeds up. Once
+// every player has joined, the server sends the whole game after each tick:
+//
+//   update 57 playing -
+//   scores 3 1 0
+//   grid 5 2 solid
+//   #A...
+//   ..SS.
+//   snake alive U 2,1
+//   snake alive L 3,1
+//   snake dead R 0,0
+//   end
+//
+// that is the tick, the state (`playing`, `over` or `won`) and the winner
+// once the game is over (`-` for none), then the cells of the board with
+// `S` for snakes, and the snakes from head to tail. The client sends
+//
+//   turn U
+//
+// to steer its snake, with the directions of replay files, and leaves by
+// hanging up. The snake of a player who left dies.
+
+const MAGIC: &str = "snake-net";
+const VERSION: u32 = 1;
+
+pub const DEFAULT_PORT: u16 = 4321;
+pub const MAX_PLAYERS: usize = 4;
+
+// How long either side waits for the other one to introduce itself
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// A client that does not take updates for this long is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Limits on what a peer may send, so a bad one cannot make the other side
+// buffer or allocate without limit
+pub const MAX_GRID_CELLS: usize = 1 << 16;
+const MAX_LINE_LENGTH: usize = 1 << 20;
+// The header, the rows of the tallest board and a line per snake
+const MAX_UPDATE_LINES: usize = 3 + MAX_GRID_CELLS/MIN_GRID_WIDTH + MAX_PLAYERS;
+
+#[derive(Debug, PartialEq)]
+pub enum NetError {
+    Bind,
+    Connect,
+    Disconnected,
+    UnsupportedVersion,
+    Malformed,
+    Game(GameError),
+}
+
+// The whole game as sent to clients after every tick.
+pub struct Update {
+    pub tick: usize,
+    pub state: State,
+    pub winner: Option<usize>,
+    pub scores: Vec<usize>,
+    pub grid: Grid,
+    pub snakes: Vec<Snake>,
+    pub alive: Vec<bool>,
+}
+
+impl Update {
+    pub fn from_engine(engine: &Engine, tick: usize) -> Self {
+        let source = engine.grid();
+        let mut grid = Grid::new(source.width(), source.height());
+        grid.set_walls(source.walls());
+        for i in 0..grid.size() {
+            grid[i] = source[i];
+        }
+        let snakes = engine.snakes().iter()
+            .map(|snake| {
+                let segments = snake.segments().copied().collect::<Vec<_>>();
+                Snake::from_segments(&segments, snake.dir())
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            tick,
+            state: engine.state(),
+            winner: engine.winner(),
+            scores: engine.scores().to_vec(),
+            grid,
+            alive: (0..snakes.len()).map(|i| engine.is_alive(i)).collect(),
+            snakes,
+        }
+    }
+
+    pub fn serialize(&self) -> String {
+        let state = match self.state {
+            State::Playing => "playing",
+            State::GameOver => "over",
+            State::Won => "won",
+        };
+        let winner = match self.winner {
+            Some(player) => (player + 1).to_string(),
+            None => String::from("-"),
+        };
+        let scores = self.scores.iter()
+            .map(|points| points.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let walls = match self.grid.walls() {
+            Walls::Solid => "solid",
+            Walls::Open => "open",
+        };
+        let mut output = format!(
+            "update {} {} {}\nscores {}\ngrid {} {} {}\n",
+            self.tick,
+            state,
+            winner,
+            scores,
+            self.grid.width(),
+            self.grid.height(),
+            walls,
+        );
+
+        let width = self.grid.width();
+        for y in 0..self.grid.height() {
+            for x in 0..width {
+                output.push(match self.grid[y*width + x] {
+                    Cell::Empty => '.',
+                    Cell::Snake => 'S',
+                    Cell::Apple => 'A',
+                    Cell::Wall => '#',
+                });
+            }
+            output.push('\n');
+        }
+
+        for (snake, alive) in self.snakes.iter().zip(self.alive.iter()) {
+            let segments = snake.segments()
+                .map(|segment| format!(" {},{}", segment.x, segment.y))
+                .collect::<String>();
+            output.push_str(&format!(
+                "snake {} {}{}\n",
+                if *alive { "alive" } else { "dead" },
+                dir_symbol(snake.dir()),
+                segments,
+            ));
+        }
+        output.push_str("end\n");
+
+        output
+    }
+
+    // Inverse of `serialize`, from the lines up to and without `end`.
+    pub fn parse(lines: &[String]) -> Result<Self, NetError> {
+        let mut lines = lines.iter().map(String::as_str);
+        let mut next = |key: &str| match lines.next().and_then(|line| line.split_once(' ')) {
+            Some((k, value)) if k == key => Ok(value),
+            _ => Err(NetError::Malformed),
+        };
+
+        let header = next("update")?.split(' ').collect::<Vec<_>>();
+        let (tick, state, winner) = match header[..] {
+            [tick, state, winner] => (tick, state, winner),
+            _ => return Err(NetError::Malformed),
+        };
+        let tick = parse_number(tick)?;
+        let state = match state {
+            "playing" => State::Playing,
+            "over" => State::GameOver,
+            "won" => State::Won,
+            _ => return Err(NetError::Malformed),
+        };
+        let winner = match winner {
+            "-" => None,
+            player => match parse_number(player)? {
+                0 => return Err(NetError::Malformed),
+                player => Some(player - 1),
+            },
+        };
+        let scores = next("scores")?.split(' ')
+            .map(parse_number)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let size = next("grid")?.split(' ').collect::<Vec<_>>();
+        let (width, height, walls) = match size[..] {
+            [width, height, walls] => (parse_number(width)?, parse_number(height)?, walls),
+            _ => return Err(NetError::Malformed),
+        };
+        match width.checked_mul(height) {
+            Some(cells) if cells <= MAX_GRID_CELLS => {},
+            _ => return Err(NetError::Malformed),
+        }
+        let mut grid = Grid::new(width, height);
+        grid.set_walls(match walls {
+            "solid" => Walls::Solid,
+            "open" => Walls::Open,
+            _ => return Err(NetError::Malformed),
+        });
+        for y in 0..height {
+            let row = lines.next().unwrap_or_default().chars().collect::<Vec<_>>();
+            if row.len() != width {
+                return Err(NetError::Malformed);
+            }
+            for (x, symbol) in row.into_iter().enumerate() {
+                grid[y*width + x] = match symbol {
+                    '.' => Cell::Empty,
+                    'S' => Cell::Snake,
+                    'A' => Cell::Apple,
+                    '#' => Cell::Wall,
+                    _ => return Err(NetError::Malformed),
+                };
+            }
+        }
+
+        let mut snakes = Vec::new();
+        let mut alive = Vec::new();
+        for line in lines {
+            let mut words = line.split(' ');
+            if words.next() != Some("snake") {
+                return Err(NetError::Malformed);
+            }
+            alive.push(match words.next() {
+                Some("alive") => true,
+                Some("dead") => false,
+                _ => return Err(NetError::Malformed),
+            });
+            let dir = match words.next().and_then(parse_dir) {
+                Some(dir) => dir,
+                None => return Err(NetError::Malformed),
+            };
+            let segments = words
+                .map(|segment| {
+                    let (x, y) = segment.split_once(',')?;
+                    let position = Position { x: x.parse().ok()?, y: y.parse().ok()? };
+                    (position.x < width && position.y < height).then_some(position)
+                })
+                .collect::<Option<Vec<_>>>();
+            match segments {
+                Some(segments) if !segments.is_empty() => {
+                    snakes.push(Snake::from_segments(&segments, dir));
+                },
+                _ => return Err(NetError::Malformed),
+            }
+        }
+        if snakes.len() != scores.len() {
+            return Err(NetError::Malformed);
+        }
+
+        Ok(Self {
+            tick,
+            state,
+            winner,
+            scores,
+            grid,
+            snakes,
+            alive,
+        })
+    }
+}
+
+fn parse_number(value: &str) -> Result<usize, NetError> {
+    match value.parse::<usize>() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(NetError::Malformed),
+    }
+}
+
+fn dir_symbol(dir: Direction) -> char {
+    match dir {
+        Direction::Left => 'L',
+        Direction::Down => 'D',
+        Direction::Up => 'U',
+        Direction::Right => 'R',
+    }
+}
+
+fn parse_dir(symbol: &str) -> Option<Direction> {
+    match symbol {
+        "L" => Some(Direction::Left),
+        "D" => Some(Direction::Down),
+        "U" => Some(Direction::Up),
+        "R" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+// Parses an update for a game of `players` snakes, as the welcome said.
+fn parse_update(lines: &[String], players: usize) -> Result<Update, NetError> {
+    let update = Update::parse(lines)?;
+    if update.snakes.len() != players {
+        return Err(NetError::Malformed);
+    }
+
+    Ok(update)
+}
+
+fn send(stream: &mut TcpStream, text: &str) -> Result<(), NetError> {
+    match stream.write_all(text.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(NetError::Disconnected),
+    }
+}
+
+// Sends `MAGIC VERSION` and checks that the other side sent the same.
+fn handshake(
+    stream: &mut TcpStream,
+    reader: &mut BufReader<TcpStream>,
+) -> Result<(), NetError> {
+    send(stream, &format!("{} {}\n", MAGIC, VERSION))?;
+    let line = read_line(reader)?;
+    match line.split_once(' ') {
+        Some((MAGIC, version)) if version.parse() == Ok(VERSION) => Ok(()),
+        Some((MAGIC, _)) => Err(NetError::UnsupportedVersion),
+        _ => Err(NetError::Malformed),
+    }
+}
+
+// Reads a line of at most `MAX_LINE_LENGTH` bytes, without its line ending.
+fn read_line(reader: &mut BufReader<TcpStream>) -> Result<String, NetError> {
+    let mut line = String::new();
+    let mut limited = reader.take(MAX_LINE_LENGTH as u64 + 1);
+    match BufRead::read_line(&mut limited, &mut line) {
+        Ok(0) | Err(_) => Err(NetError::Disconnected),
+        Ok(_) if line.len() > MAX_LINE_LENGTH && !line.ends_with('\n') => {
+            Err(NetError::Malformed)
+        },
+        Ok(_) => Ok(line.trim_end().to_string()),
+    }
+}
+
+// Forwards the lines `reader` receives to `sender`, tagged with `id`, and
+// `None` once the other side hangs up.
+fn forward_lines(
+    id: usize,
+    mut reader: BufReader<TcpStream>,
+    sender: Sender<(usize, Option<String>)>,
+) {
+    thread::spawn(move || {
+        // A line too long to be anything we sent counts as hanging up
+        while let Ok(line) = read_line(&mut reader) {
+            if sender.send((id, Some(line))).is_err() {
+                return;
+            }
+        }
+        let _ = sender.send((id, None));
+    });
+}
+
+fn configure(stream: &TcpStream, read: Option<Duration>) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(read)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))
+}
+
+// Runs the game for players connected over TCP. The server alone moves the
+// snakes; clients only send turns and draw what they are sent.
+pub struct Server {
+    engine: Engine,
+    speed: Speed,
+    // One per snake, `None` until the player joins and after they leave
+    clients: Vec<Option<TcpStream>>,
+    // Computer players, by snake, which take the place of a client
+    pilots: Vec<Option<Box<dyn Pilot>>>,
+    sender: Sender<(usize, Option<String>)>,
+    receiver: Receiver<(usize, Option<String>)>,
+    spectators: Option<Spectators>,
+}
+
+impl Server {
+    pub fn new(
+        level: &Level,
+        walls: Walls,
+        seed: u64,
+        speed: Speed,
+        spawns: &[(Position, Direction)],
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            engine: Engine::with_spawns(level, walls, seed, spawns),
+            speed,
+            clients: spawns.iter().map(|_| None).collect(),
+            pilots: spawns.iter().map(|_| None).collect(),
+            sender,
+            receiver,
+            spectators: None,
+        }
+    }
+
+    // Shows the game to `spectators` as it is played.
+    pub fn set_spectators(&mut self, spectators: Spectators) {
+        self.spectators = Some(spectators);
+    }
+
+    // Lets `pilot` play the snake of `player`, which no client can join as.
+    pub fn set_pilot(&mut self, player: usize, pilot: Box<dyn Pilot>) {
+        self.pilots[player] = Some(pilot);
+    }
+
+    pub fn bind(port: u16) -> Result<TcpListener, NetError> {
+        match TcpListener::bind(("0.0.0.0", port)) {
+            Ok(listener) => Ok(listener),
+            Err(_) => Err(NetError::Bind),
+        }
+    }
+
+    // Players who left while waiting for the others free their seats.
+    pub fn is_full(&mut self) -> bool {
+        self.forget_departed();
+        self.free_seat().is_none()
+    }
+
+    // Frees the seats of players who hung up before the game started.
+    // Anything else they sent is of no use yet.
+    fn forget_departed(&mut self) {
+        while let Ok((player, line)) = self.receiver.try_recv() {
+            if line.is_none() {
+                self.clients[player] = None;
+            }
+        }
+    }
+
+    fn free_seat(&self) -> Option<usize> {
+        (0..self.clients.len())
+            .find(|&player| self.clients[player].is_none() && self.pilots[player].is_none())
+    }
+
+    // Waits for the next client and welcomes it. Returns its player number,
+    // from 0.
+    pub fn accept(&mut self, listener: &TcpListener) -> Result<usize, NetError> {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(_) => return Err(NetError::Connect),
+        };
+        self.forget_departed();
+        let player = match self.free_seat() {
+            Some(player) => player,
+            None => return Err(NetError::Connect),
+        };
+        let mut reader = match stream.try_clone() {
+            Ok(clone) => BufReader::new(clone),
+            Err(_) => return Err(NetError::Connect),
+        };
+        if configure(&stream, Some(HANDSHAKE_TIMEOUT)).is_err() {
+            return Err(NetError::Connect);
+        }
+        handshake(&mut stream, &mut reader)?;
+        if stream.set_read_timeout(None).is_err() {
+            return Err(NetError::Connect);
+        }
+
+        send(&mut stream, &format!(
+            "welcome {} {} {} {}\n",
+            player + 1,
+            self.clients.len(),
+            self.speed.difficulty.name(),
+            if self.speed.progressive { "on" } else { "off" },
+        ))?;
+        forward_lines(player, reader, self.sender.clone());
+        self.clients[player] = Some(stream);
+
+        Ok(player)
+    }
+
+    // Plays the game to its end. A player who hangs up loses their snake.
+    pub fn run(&mut self) {
+        let players = self.clients.len();
+        let mut inputs = vec![Vec::new(); players];
+        let mut tick = 0;
+        let mut next_tick = Instant::now();
+        self.broadcast(tick);
+        while self.engine.state() == State::Playing {
+            next_tick += self.speed.tick_duration(self.engine.points());
+            let now = Instant::now();
+            if next_tick > now {
+                thread::sleep(next_tick - now);
+            } else {
+                // Do not rush to catch up after a stall
+                next_tick = now;
+            }
+
+            loop {
+                match self.receiver.try_recv() {
+                    Ok((player, Some(line))) => {
+                        let dir = line.strip_prefix("turn ").and_then(parse_dir);
+                        if let Some(dir) = dir {
+                            inputs[player].push(dir);
+                        }
+                    },
+                    Ok((player, None)) => self.drop_client(player),
+                    Err(_) => break,
+                }
+            }
+
+            for (player, pilot) in self.pilots.iter_mut().enumerate() {
+                if let Some(pilot) = pilot {
+                    if self.engine.is_alive(player) {
+                        inputs[player].extend(pilot.steer(&self.engine, player));
+                    }
+                }
+            }
+
+            let player_inputs = inputs.iter()
+                .map(Vec::as_slice)
+                .collect::<Vec<_>>();
+            self.engine.tick_players(&player_inputs);
+            inputs.iter_mut().for_each(Vec::clear);
+            tick += 1;
+            self.broadcast(tick);
+        }
+
+        for stream in self.clients.iter_mut().filter_map(Option::take) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        if let Some(spectators) = self.spectators.as_mut() {
+            let result = match self.engine.winner() {
+                Some(player) => format!(" Player {} wins! ", player + 1),
+                None => String::from(" Draw! "),
+            };
+            spectators.render_message(&[result]);
+        }
+    }
+
+    fn broadcast(&mut self, tick: usize) {
+        if let Some(spectators) = self.spectators.as_mut() {
+            let snapshot = Snapshot {
+                grid: self.engine.grid(),
+                snakes: self.engine.snakes(),
+                scores: self.engine.scores(),
+                speed: self.speed,
+                status: "",
+                dimmed: false,
+            };
+            spectators.render(&snapshot);
+        }
+
+        let update = Update::from_engine(&self.engine, tick).serialize();
+        for player in 0..self.clients.len() {
+            let sent = match self.clients[player].as_mut() {
+                Some(stream) => send(stream, &update).is_ok(),
+                None => continue,
+            };
+            if !sent {
+                self.drop_client(player);
+            }
+        }
+    }
+
+    fn drop_client(&mut self, player: usize) {
+        if let Some(stream) = self.clients[player].take() {
+            let _ = stream.shutdown(Shutdown::Both);
+            self.engine.kill(player);
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.engine.state()
+    }
+
+    pub fn scores(&self) -> &[usize] {
+        self.engine.scores()
+    }
+
+    pub fn winner(&self) -> Option<usize> {
+        self.engine.winner()
+    }
+}
+
+// Which way a key steers the player's snake. Both the arrow keys, hjkl and
+// WASD work, as every player has a keyboard of their own.
+fn turn(key: Key) -> Option<Direction> {
+    match key {
+        Key::Left  | Key::Char('h') | Key::Char('a') => Some(Direction::Left),
+        Key::Down  | Key::Char('j') | Key::Char('s') => Some(Direction::Down),
+        Key::Up    | Key::Char('k') | Key::Char('w') => Some(Direction::Up),
+        Key::Right | Key::Char('l') | Key::Char('d') => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+// A player connected to a `Server`, drawing the game as the server sends it.
+pub struct Client<R: Renderer> {
+    stream: TcpStream,
+    receiver: Receiver<(usize, Option<String>)>,
+    player: usize,
+    players: usize,
+    speed: Speed,
+    update: Option<Update>,
+    stdin: Option<Events<AsyncReader>>,
+    renderer: R,
+}
+
+impl<R: Renderer> Client<R> {
+    pub fn connect(addr: &str, renderer: R) -> Result<Self, NetError> {
+        let mut stream = match TcpStream::connect(addr) {
+            Ok(stream) => stream,
+            Err(_) => return Err(NetError::Connect),
+        };
+        let mut reader = match stream.try_clone() {
+            Ok(clone) => BufReader::new(clone),
+            Err(_) => return Err(NetError::Connect),
+        };
+        if configure(&stream, Some(HANDSHAKE_TIMEOUT)).is_err() {
+            return Err(NetError::Connect);
+        }
+        handshake(&mut stream, &mut reader)?;
+
+        let welcome = read_line(&mut reader)?;
+        let words = welcome.split(' ').collect::<Vec<_>>();
+        let (player, players, speed) = match words[..] {
+            ["welcome", player, players, difficulty, progressive] => {
+                let difficulty = Difficulty::from_name(difficulty);
+                let progressive = match progressive {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => None,
+                };
+                match (parse_number(player)?, parse_number(players)?, difficulty, progressive) {
+                    (player, players, Some(difficulty), Some(progressive))
+                        if (1..=players).contains(&player) =>
+                    {
+                        (player - 1, players, Speed { difficulty, progressive })
+                    },
+                    _ => return Err(NetError::Malformed),
+                }
+            },
+            _ => return Err(NetError::Malformed),
+        };
+        if stream.set_read_timeout(None).is_err() {
+            return Err(NetError::Connect);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        forward_lines(player, reader, sender);
+
+        Ok(Self {
+            stream,
+            receiver,
+            player,
+            players,
+            speed,
+            update: None,
+            stdin: None,
+            renderer,
+        })
+    }
+
+    // Player number, from 0
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    // The last state of the game received from the server
+    pub fn update(&self) -> Option<&Update> {
+        self.update.as_ref()
+    }
+
+    fn next_event(&mut self) -> Option<io::Result<Event>> {
+        self.stdin
+            .get_or_insert_with(|| termion::async_stdin().events())
+            .next()
+    }
+
+    // Plays until the game is over or the player quits with `q`.
+    pub fn run(&mut self) -> Result<(), NetError> {
+        if let Err(e) = self.renderer.open() {
+            return Err(NetError::Game(e));
+        }
+        let result = self.run_on_screen();
+        let closed = self.renderer.close();
+
+        result?;
+        match closed {
+            Ok(_) => Ok(()),
+            Err(e) => Err(NetError::Game(e)),
+        }
+    }
+
+    fn run_on_screen(&mut self) -> Result<(), NetError> {
+        let mut size = self.size()?;
+        let mut lines = Vec::new();
+        let mut redraw = true;
+        loop {
+            let new_size = self.size()?;
+            if new_size != size {
+                size = new_size;
+                redraw = true;
+                if let Err(e) = self.renderer.clear() {
+                    return Err(NetError::Game(e));
+                }
+            }
+
+            while let Some(Ok(event)) = self.next_event() {
+                let key = match event {
+                    Event::Key(key) => key,
+                    _ => continue,
+                };
+                if matches!(key, Key::Char('q') | Key::Esc | Key::Ctrl('c')) {
+                    return Ok(());
+                }
+                if let Some(dir) = turn(key) {
+                    send(&mut self.stream, &format!("turn {}\n", dir_symbol(dir)))?;
+                }
+            }
+
+            loop {
+                match self.receiver.try_recv() {
+                    Ok((_, Some(line))) if line == "end" => {
+                        self.update = Some(parse_update(&lines, self.players)?);
+                        lines.clear();
+                        redraw = true;
+                    },
+                    Ok((_, Some(_))) if lines.len() == MAX_UPDATE_LINES => {
+                        return Err(NetError::Malformed);
+                    },
+                    Ok((_, Some(line))) => lines.push(line),
+                    Ok((_, None)) | Err(TryRecvError::Disconnected) => {
+                        // The server hangs up once the game is over
+                        if self.is_over() {
+                            break;
+                        }
+                        return Err(NetError::Disconnected);
+                    },
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+
+            if redraw {
+                self.render(size)?;
+                redraw = false;
+            }
+            if self.is_over() {
+                break;
+            }
+
+            thread::sleep(INPUT_POLL_INTERVAL);
+        }
+
+        let result = match self.update.as_ref().and_then(|update| update.winner) {
+            Some(player) if player == self.player => String::from(" You win! "),
+            Some(player) => format!(" Player {} wins! ", player + 1),
+            None => String::from(" Draw! "),
+        };
+        let lines = [
+            result,
+            String::from(" Press any key to exit "),
+        ];
+        if let Err(e) = self.renderer.render_message(&lines) {
+            return Err(NetError::Game(e));
+        }
+        // Keys pressed while steering would otherwise dismiss the message
+        // right away
+        while let Some(Ok(_)) = self.next_event() {}
+        while !matches!(self.next_event(), Some(Ok(Event::Key(_)))) {
+            thread::sleep(INPUT_POLL_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    fn is_over(&self) -> bool {
+        self.update.as_ref().is_some_and(|update| update.state != State::Playing)
+    }
+
+    fn size(&self) -> Result<(u16, u16), NetError> {
+        match self.renderer.size() {
+            Ok(size) => Ok(size),
+            Err(e) => Err(NetError::Game(e)),
+        }
+    }
+
+    fn render(&mut self, (col_count, row_count): (u16, u16)) -> Result<(), NetError> {
+        let update = match self.update.as_ref() {
+            Some(update) => update,
+            None => {
+                let lines = [
+                    String::from(" Waiting for other players "),
+                    format!(" You are player {} of {} ", self.player + 1, self.players),
+                    String::from(" Q: leave "),
+                ];
+                if let Err(e) = self.renderer.clear() {
+                    return Err(NetError::Game(e));
+                }
+                return match self.renderer.render_message(&lines) {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(NetError::Game(e)),
+                };
+            },
+        };
+
+        let (width, height) = self.renderer.board_size(&update.grid);
+        let result = if width > col_count as usize || height > row_count as usize {
+            let lines = [
+                String::from("Resize your terminal"),
+                format!("({}x{} needed)", width, height),
+            ];
+            self.renderer.render_message(&lines)
+        } else {
+            let out = if update.alive[self.player] { "" } else { " (out)" };
+            let status = format!("  You: P{}{}", self.player + 1, out);
+            let snapshot = Snapshot {
+                grid: &update.grid,
+                snakes: &update.snakes,
+                scores: &update.scores,
+                speed: self.speed,
+                status: &status,
+                dimmed: false,
+            };
+            self.renderer.render(&snapshot)
+        };
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(NetError::Game(e)),
+        }
+    }
+}
+
+// The line reader holds on to the connection too, so it has to be closed
+// explicitly for the server to notice the player left.
+impl<R: Renderer> Drop for Client<R> {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Pathfinder;
+    use crate::renderer::MemoryRenderer;
+
+    fn two_player_engine() -> Engine {
+        let level = Level::parse("\
+A.....
+^.....
+......
+").unwrap();
+        Engine::with_spawns(&level, Walls::Solid, 0, &[
+            (Position { x: 1, y: 1 }, Direction::Right),
+            (Position { x: 4, y: 2 }, Direction::Left),
+        ])
+    }
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines()
+            .take_while(|line| *line != "end")
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut engine = two_player_engine();
+        engine.tick_players(&[&[Direction::Up], &[]]);
+
+        assert_eq!(Update::from_engine(&engine, 1).serialize(), "\
+update 1 playing -
+scores 0 0
+grid 6 3 solid
+AS....
+......
+...S..
+snake alive U 1,0
+snake alive L 3,2
+end
+");
+    }
+
+    #[test]
+    fn test_parse_is_inverse_of_serialize() {
+        let mut engine = two_player_engine();
+        engine.tick_players(&[&[], &[Direction::Up]]);
+        engine.kill(0);
+        let text = Update::from_engine(&engine, 1).serialize();
+
+        let update = Update::parse(&lines(&text)).unwrap();
+
+        assert_eq!(update.state, State::GameOver);
+        assert_eq!(update.winner, Some(1));
+        assert_eq!(update.alive, [false, true]);
+        assert_eq!(update.grid.render(), engine.grid().render());
+        assert_eq!(update.serialize(), text);
+    }
+
+    #[test]
+    fn test_parse_when_update_is_malformed_then_fails() {
+        let text = Update::from_engine(&two_player_engine(), 0).serialize();
+        let cases = [
+            text.replace("playing", "paused"),
+            text.replace("A.....", "A...."),
+            text.replace("A.....", "A....?"),
+            text.replace("L 4,2", "L 6,2"),
+            text.replace("snake alive L 4,2\n", ""),
+            text.replace("grid 6 3", "grid 4294967296 4294967296"),
+            text.replace("grid 6 3", "grid 65536 2"),
+        ];
+
+        for case in cases {
+            assert_eq!(Update::parse(&lines(&case)).err(), Some(NetError::Malformed));
+        }
+    }
+
+    #[test]
+    fn test_parse_update_when_snakes_are_missing_then_fails() {
+        let text = Update::from_engine(&two_player_engine(), 0).serialize();
+
+        assert!(parse_update(&lines(&text), 2).is_ok());
+        assert_eq!(parse_update(&lines(&text), 3).err(), Some(NetError::Malformed));
+    }
+
+    #[test]
+    fn test_server_and_client_play_over_localhost() {
+        // Tall enough for the snakes to be alive when the first player leaves
+        let level = Level::empty(8, 40);
+        let speed = Speed { difficulty: Difficulty::Insane, progressive: false };
+        let spawns = level.spread_spawns(2).unwrap();
+        let mut server = Server::new(&level, Walls::Solid, 0, speed, &spawns);
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let clients = thread::spawn(move || {
+            let renderer = || MemoryRenderer::new((80, 24));
+            let first = Client::connect(&addr, renderer()).unwrap();
+            let second = Client::connect(&addr, renderer()).unwrap();
+            (first, second)
+        });
+        assert_eq!(server.accept(&listener), Ok(0));
+        assert_eq!(server.accept(&listener), Ok(1));
+        assert!(server.is_full());
+        let (first, second) = clients.join().unwrap();
+        assert_eq!((first.player(), second.player()), (0, 1));
+
+        // The first player leaves once the game has started
+        let leaving = thread::spawn(move || {
+            first.receiver.recv().unwrap();
+            drop(first);
+        });
+        server.run();
+        leaving.join().unwrap();
+
+        assert_eq!(server.state(), State::GameOver);
+        assert_eq!(server.winner(), Some(1));
+        let lines = second.receiver.iter()
+            .map_while(|(_, line)| line)
+            .collect::<Vec<_>>();
+        let last = lines[..lines.len() - 1].rsplit(|line| line == "end").next().unwrap();
+        let update = Update::parse(last).unwrap();
+        assert_eq!(update.state, State::GameOver);
+        assert_eq!(update.winner, Some(1));
+        assert_eq!(update.alive, [false, true]);
+    }
+
+    #[test]
+    fn test_accept_when_a_player_left_before_the_game_starts_then_frees_their_seat() {
+        let level = Level::empty(8, 8);
+        let spawns = [(level.spawn, level.dir)];
+        let mut server = Server::new(&level, Walls::Solid, 0, Speed::default(), &spawns);
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let join = |addr: String| {
+            thread::spawn(move || Client::connect(&addr, MemoryRenderer::new((80, 24))).unwrap())
+        };
+
+        let first = join(addr.clone());
+        assert_eq!(server.accept(&listener), Ok(0));
+        drop(first.join().unwrap());
+        while server.is_full() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let second = join(addr);
+
+        assert_eq!(server.accept(&listener), Ok(0));
+        assert!(server.is_full());
+        assert_eq!(second.join().unwrap().player(), 0);
+    }
+
+    #[test]
+    fn test_is_full_when_pilots_take_every_seat() {
+        let level = Level::empty(8, 8);
+        let spawns = level.spread_spawns(2).unwrap();
+        let mut server = Server::new(&level, Walls::Solid, 0, Speed::default(), &spawns);
+
+        server.set_pilot(0, Box::new(Pathfinder::new()));
+        assert!(!server.is_full());
+        server.set_pilot(1, Box::new(Pathfinder::new()));
+        assert!(server.is_full());
+    }
+
+    #[test]
+    fn test_handshake_when_version_differs_then_fails() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let peer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            send(&mut stream, &format!("{} {}\n", MAGIC, VERSION + 1)).unwrap();
+            read_line(&mut reader).unwrap()
+        });
+        let result = Client::connect(&addr.to_string(), MemoryRenderer::new((80, 24)));
+
+        assert_eq!(result.err(), Some(NetError::UnsupportedVersion));
+        assert_eq!(peer.join().unwrap(), format!("{} {}", MAGIC, VERSION));
+    }
+
+    #[test]
+    fn test_read_line_when_line_is_too_long_then_fails() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let longest = "a".repeat(MAX_LINE_LENGTH);
+        let text = format!("{}\n{}a\n", longest, longest);
+
+        let peer = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            send(&mut stream, &text).unwrap();
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+
+        assert_eq!(read_line(&mut reader), Ok(longest));
+        assert_eq!(read_line(&mut reader), Err(NetError::Malformed));
+        peer.join().unwrap();
+    }
+}
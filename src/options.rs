@@ -0,0 +1,466 @@
+use std::path::PathBuf;
+
+use crate::difficulty::Difficulty;
+use crate::glyphs::Glyphs;
+use crate::net::{ DEFAULT_PORT, MAX_PLAYERS };
+use crate::theme::Theme;
+use crate::grid::{
+    DEFAULT_GRID_WIDTH,
+    DEFAULT_GRID_HEIGHT,
+    MIN_GRID_WIDTH,
+    MIN_GRID_HEIGHT,
+    Layout,
+    Walls,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum OptionsError {
+    InvalidValue(String),
+    MissingValue(String),
+    UnknownArgument(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Play,
+    Replay(PathBuf),
+    Scores,
+    Serve,
+    Join(String),
+    Demo,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub width: usize,
+    pub height: usize,
+    pub fit: bool,
+    pub walls: Walls,
+    // The player picks one from a menu when not given
+    pub difficulty: Option<Difficulty>,
+    pub progressive: bool,
+    pub two_player: bool,
+    // Whether the computer plays the last snake
+    pub cpu: bool,
+    // Snakes in a networked game
+    pub players: usize,
+    pub port: u16,
+    // Port to show the game on to read-only spectators
+    pub spectate: Option<u16>,
+    pub level: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub record: Option<PathBuf>,
+    pub plain: bool,
+    pub theme: Option<&'static Theme>,
+    pub glyphs: Option<&'static Glyphs>,
+    pub layout: Layout,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Command::Play,
+            width: DEFAULT_GRID_WIDTH,
+            height: DEFAULT_GRID_HEIGHT,
+            fit: false,
+            walls: Walls::Solid,
+            difficulty: None,
+            progressive: false,
+            two_player: false,
+            cpu: false,
+            players: 2,
+            port: DEFAULT_PORT,
+            spectate: None,
+            level: None,
+            seed: None,
+            record: None,
+            plain: false,
+            theme: None,
+            glyphs: None,
+            layout: Layout::Outline,
+        }
+    }
+}
+
+impl Options {
+    pub fn parse<I>(args: I) -> Result<Self, OptionsError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter().peekable();
+
+        if args.peek().is_some_and(|arg| arg == "replay") {
+            let arg = args.next().unwrap();
+            let value = require_value(&arg, args.next())?;
+            options.command = Command::Replay(PathBuf::from(value));
+        } else if args.peek().is_some_and(|arg| arg == "serve") {
+            args.next();
+            options.command = Command::Serve;
+        } else if args.peek().is_some_and(|arg| arg == "join") {
+            let arg = args.next().unwrap();
+            let value = require_value(&arg, args.next())?;
+            options.command = Command::Join(value);
+        } else if args.peek().is_some_and(|arg| arg == "demo") {
+            args.next();
+            options.command = Command::Demo;
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => {
+                    let value = require_value(&arg, args.next())?;
+                    options.width = parse_number(&arg, &value, MIN_GRID_WIDTH)?;
+                },
+                "--height" => {
+                    let value = require_value(&arg, args.next())?;
+                    options.height = parse_number(&arg, &value, MIN_GRID_HEIGHT)?;
+                },
+                "--level" => {
+                    let value = require_value(&arg, args.next())?;
+                    options.level = Some(PathBuf::from(value));
+                },
+                "--scores" => {
+                    options.command = Command::Scores;
+                },
+                "--record" => {
+                    let value = require_value(&arg, args.next())?;
+                    options.record = Some(PathBuf::from(value));
+                },
+                "--seed" => {
+                    let value = require_value(&arg, args.next())?;
+                    match value.parse::<u64>() {
+                        Ok(seed) => options.seed = Some(seed),
+                        Err(_) => {
+                            return Err(OptionsError::InvalidValue(arg));
+                        },
+                    }
+                },
+                "--fit" => {
+                    options.fit = true;
+                },
+                "--wrap" => {
+                    options.walls = Walls::Open;
+                },
+                "--two-player" => {
+                    options.two_player = true;
+                },
+                "--cpu" => {
+                    options.cpu = true;
+                },
+                "--players" => {
+                    let value = require_value(&arg, args.next())?;
+                    match parse_number(&arg, &value, 2)? {
+                        players if players <= MAX_PLAYERS => options.players = players,
+                        _ => return Err(OptionsError::InvalidValue(arg)),
+                    }
+                },
+                "--port" => {
+                    let value = require_value(&arg, args.next())?;
+                    match value.parse::<u16>() {
+                        Ok(port) => options.port = port,
+                        Err(_) => return Err(OptionsError::InvalidValue(arg)),
+                    }
+                },
+                "--spectate" => {
+                    let value = require_value(&arg, args.next())?;
+                    match value.parse::<u16>() {
+                        Ok(port) => options.spectate = Some(port),
+                        Err(_) => return Err(OptionsError::InvalidValue(arg)),
+                    }
+                },
+                "--plain" => {
+                    options.plain = true;
+                },
+                "--theme" => {
+                    let value = require_value(&arg, args.next())?;
+                    match Theme::from_name(&value) {
+                        Some(theme) => options.theme = Some(theme),
+                        None => return Err(OptionsError::InvalidValue(arg)),
+                    }
+                },
+                "--glyphs" => {
+                    let value = require_value(&arg, args.next())?;
+                    match Glyphs::from_name(&value) {
+                        Some(glyphs) => options.glyphs = Some(glyphs),
+                        None => return Err(OptionsError::InvalidValue(arg)),
+                    }
+                },
+                "--layout" => {
+                    let value = require_value(&arg, args.next())?;
+                    match Layout::from_name(&value) {
+                        Some(layout) => options.layout = layout,
+                        None => return Err(OptionsError::InvalidValue(arg)),
+                    }
+                },
+                "--difficulty" => {
+                    let value = require_value(&arg, args.next())?;
+                    match Difficulty::from_name(&value) {
+                        Some(difficulty) => options.difficulty = Some(difficulty),
+                        None => return Err(OptionsError::InvalidValue(arg)),
+                    }
+                },
+                "--progressive" => {
+                    options.progressive = true;
+                },
+                _ => return Err(OptionsError::UnknownArgument(arg)),
+            }
+        }
+
+        // Games with more than one snake are not recorded
+        if options.record.is_some() && (options.two_player || options.cpu) {
+            return Err(OptionsError::InvalidValue("--record".to_string()));
+        }
+
+        Ok(options)
+    }
+}
+
+fn require_value(
+    flag: &str,
+    value: Option<String>,
+) -> Result<String, OptionsError> {
+    match value {
+        Some(value) => Ok(value),
+        None => Err(OptionsError::MissingValue(flag.to_string())),
+    }
+}
+
+fn parse_number(
+    flag: &str,
+    value: &str,
+    min: usize,
+) -> Result<usize, OptionsError> {
+    match value.parse::<usize>() {
+        Ok(n) if n >= min => Ok(n),
+        _ => Err(OptionsError::InvalidValue(flag.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, OptionsError> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_when_no_arguments_then_uses_defaults() {
+        assert_eq!(parse(&[]), Ok(Options::default()));
+    }
+
+    #[test]
+    fn test_parse_grid_size() {
+        let options = parse(&["--width", "10", "--height", "5"]).unwrap();
+
+        assert_eq!(options.width, 10);
+        assert_eq!(options.height, 5);
+    }
+
+    #[test]
+    fn test_parse_fit() {
+        let options = parse(&["--fit"]).unwrap();
+
+        assert!(options.fit);
+    }
+
+    #[test]
+    fn test_parse_wrap() {
+        let options = parse(&["--wrap"]).unwrap();
+
+        assert_eq!(options.walls, Walls::Open);
+    }
+
+    #[test]
+    fn test_parse_difficulty() {
+        let options = parse(&["--difficulty", "hard", "--progressive"]).unwrap();
+
+        assert_eq!(options.difficulty, Some(Difficulty::Hard));
+        assert!(options.progressive);
+        assert_eq!(
+            parse(&["--difficulty", "medium"]),
+            Err(OptionsError::InvalidValue("--difficulty".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_two_player() {
+        let options = parse(&["--two-player"]).unwrap();
+
+        assert!(options.two_player);
+    }
+
+    #[test]
+    fn test_parse_cpu() {
+        let options = parse(&["serve", "--players", "3", "--cpu"]).unwrap();
+
+        assert!(options.cpu);
+        assert!(!options.two_player);
+    }
+
+    #[test]
+    fn test_parse_plain() {
+        let options = parse(&["replay", "best.replay", "--plain"]).unwrap();
+
+        assert!(options.plain);
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let options = parse(&["--theme", "mono"]).unwrap();
+
+        assert_eq!(options.theme, Some(Theme::mono()));
+        assert_eq!(
+            parse(&["--theme", "rainbow"]),
+            Err(OptionsError::InvalidValue("--theme".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_glyphs() {
+        let options = parse(&["--glyphs", "ascii"]).unwrap();
+
+        assert_eq!(options.glyphs.map(|glyphs| glyphs.name), Some("ascii"));
+        assert_eq!(
+            parse(&["--glyphs", "dotted"]),
+            Err(OptionsError::InvalidValue("--glyphs".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let options = parse(&["--layout", "half-block", "--fit"]).unwrap();
+
+        assert_eq!(options.layout, Layout::HalfBlock);
+        assert_eq!(
+            parse(&["--layout", "tiny"]),
+            Err(OptionsError::InvalidValue("--layout".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_level() {
+        let options = parse(&["--level", "levels/box.txt"]).unwrap();
+
+        assert_eq!(options.level, Some(PathBuf::from("levels/box.txt")));
+    }
+
+    #[test]
+    fn test_parse_seed() {
+        let options = parse(&["--seed", "18446744073709551615"]).unwrap();
+
+        assert_eq!(options.seed, Some(u64::MAX));
+        assert_eq!(
+            parse(&["--seed", "-1"]),
+            Err(OptionsError::InvalidValue("--seed".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_replay_command() {
+        let options = parse(&["replay", "best.replay"]).unwrap();
+
+        assert_eq!(options.command, Command::Replay(PathBuf::from("best.replay")));
+        assert_eq!(
+            parse(&["replay"]),
+            Err(OptionsError::MissingValue("replay".to_string())),
+        );
+        assert_eq!(
+            parse(&["--wrap", "replay", "best.replay"]),
+            Err(OptionsError::UnknownArgument("replay".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_serve_command() {
+        let options = parse(&["serve", "--players", "3", "--port", "5000"]).unwrap();
+
+        assert_eq!(options.command, Command::Serve);
+        assert_eq!(options.players, 3);
+        assert_eq!(options.port, 5000);
+        assert_eq!(
+            parse(&["serve", "--players", "5"]),
+            Err(OptionsError::InvalidValue("--players".to_string())),
+        );
+        assert_eq!(
+            parse(&["serve", "--port", "65536"]),
+            Err(OptionsError::InvalidValue("--port".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_join_command() {
+        let options = parse(&["join", "localhost:5000", "--theme", "mono"]).unwrap();
+
+        assert_eq!(options.command, Command::Join("localhost:5000".to_string()));
+        assert_eq!(
+            parse(&["join"]),
+            Err(OptionsError::MissingValue("join".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_demo_command() {
+        let options = parse(&["demo", "--width", "20", "--difficulty", "insane"]).unwrap();
+
+        assert_eq!(options.command, Command::Demo);
+        assert_eq!(options.width, 20);
+        assert_eq!(options.difficulty, Some(Difficulty::Insane));
+    }
+
+    #[test]
+    fn test_parse_spectate() {
+        let options = parse(&["--spectate", "2323"]).unwrap();
+
+        assert_eq!(options.spectate, Some(2323));
+        assert_eq!(
+            parse(&["--spectate", "telnet"]),
+            Err(OptionsError::InvalidValue("--spectate".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_scores() {
+        assert_eq!(parse(&["--scores"]).unwrap().command, Command::Scores);
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let options = parse(&["--record", "run.replay"]).unwrap();
+
+        assert_eq!(options.command, Command::Play);
+        assert_eq!(options.record, Some(PathBuf::from("run.replay")));
+        for versus in ["--two-player", "--cpu"] {
+            assert_eq!(
+                parse(&["--record", "run.replay", versus]),
+                Err(OptionsError::InvalidValue("--record".to_string())),
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_when_value_is_missing_then_fails() {
+        assert_eq!(
+            parse(&["--width"]),
+            Err(OptionsError::MissingValue("--width".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_when_value_is_too_small_then_fails() {
+        assert_eq!(
+            parse(&["--height", "1"]),
+            Err(OptionsError::InvalidValue("--height".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_parse_when_argument_is_unknown_then_fails() {
+        assert_eq!(
+            parse(&["--foo"]),
+            Err(OptionsError::UnknownArgument("--foo".to_string())),
+        );
+    }
+}
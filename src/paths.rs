@@ -0,0 +1,20 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "snake";
+
+// Directory for files the game keeps between runs, following the XDG Base
+// Directory Specification: `$XDG_DATA_HOME/snake`, falling back to
+// `~/.local/share/snake`.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        let dir = PathBuf::from(dir);
+        // Relative paths are invalid according to the specification
+        if dir.is_absolute() {
+            return Some(dir.join(APP_DIR));
+        }
+    }
+
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".local/share").join(APP_DIR))
+}
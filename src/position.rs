@@ -1,17 +1,22 @@
 use crate::direction::Direction;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum PositionError {
+    OffBoard,
+}
+
 impl Position {
-    pub fn move_in_direction(&self, dir: &Direction) -> Result<Position, ()> {
+    pub fn move_in_direction(&self, dir: &Direction) -> Result<Position, PositionError> {
         let (x, y) = match dir {
             Direction::Left => {
                 if self.x == 0 {
-                    return Err(());
+                    return Err(PositionError::OffBoard);
                 }
                 (self.x - 1, self.y)
             },
@@ -20,7 +25,7 @@ impl Position {
             },
             Direction::Up => {
                 if self.y == 0 {
-                    return Err(());
+                    return Err(PositionError::OffBoard);
                 }
                 (self.x, self.y - 1)
             },
@@ -31,4 +36,20 @@ impl Position {
 
         Ok(Position { x, y })
     }
+
+    pub fn wrap_in_direction(
+        &self,
+        dir: &Direction,
+        width: usize,
+        height: usize,
+    ) -> Position {
+        let (x, y) = match dir {
+            Direction::Left => ((self.x + width - 1) % width, self.y),
+            Direction::Down => (self.x, (self.y + 1) % height),
+            Direction::Up => (self.x, (self.y + height - 1) % height),
+            Direction::Right => ((self.x + 1) % width, self.y),
+        };
+
+        Position { x, y }
+    }
 }
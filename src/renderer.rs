@@ -0,0 +1,901 @@
+use std::io::{ stdout, Stdout, Write };
+use termion::{
+    clear,
+    cursor,
+    raw::IntoRawMode,
+    raw::RawTerminal,
+    screen::AlternateScreen,
+    screen::IntoAlternateScreen,
+    style,
+};
+
+use crate::difficulty::Speed;
+use crate::direction::Direction;
+use crate::frame::{ DiffRenderer, Frame };
+use crate::game::GameError;
+use crate::glyphs::Glyphs;
+use crate::grid::{ Cell, Grid, Layout, Walls };
+use crate::position::Position;
+use crate::snake::Snake;
+use crate::theme::{ Part, Theme };
+
+pub const HEADER_HEIGHT: usize = 1;
+
+// Size assumed when the terminal cannot tell its own, as dumb ones may not
+const FALLBACK_SIZE: (u16, u16) = (80, 24);
+
+// Makes the terminal report when it gains or loses focus
+const ENABLE_FOCUS_EVENTS: &str = "\x1b[?1004h";
+const DISABLE_FOCUS_EVENTS: &str = "\x1b[?1004l";
+
+type Screen = AlternateScreen<RawTerminal<Stdout>>;
+
+pub fn terminal_size() -> Result<(u16, u16), GameError> {
+    match termion::terminal_size() {
+        Ok(size) => Ok(size),
+        Err(_) => Err(GameError::GetTerminalSize),
+    }
+}
+
+// Everything a renderer needs to draw one frame of the game.
+pub struct Snapshot<'a> {
+    pub grid: &'a Grid,
+    // One snake per player
+    pub snakes: &'a [Snake],
+    pub scores: &'a [usize],
+    pub speed: Speed,
+    // Shown after the score, e.g. the position in a replay
+    pub status: &'a str,
+    // Set while the game is paused
+    pub dimmed: bool,
+}
+
+impl Snapshot<'_> {
+    pub fn header(&self) -> String {
+        let scores = match self.scores {
+            [points] => format!("Score: {}", points),
+            scores => {
+                scores.iter()
+                    .enumerate()
+                    .map(|(i, points)| format!("P{}: {}", i + 1, points))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            },
+        };
+        let points = self.scores.iter().sum();
+        format!(
+            " {}  Speed: {} ({}){}",
+            scores,
+            self.speed.level(points),
+            self.speed.difficulty.name(),
+            self.status,
+        )
+    }
+
+    // The header and the board in `layout`, colored by `theme`.
+    pub fn frame(&self, layout: Layout, glyphs: &Glyphs, theme: &Theme) -> Frame {
+        let mut lines = vec![
+            self.header().chars().map(|c| (c, None, None)).collect::<Vec<_>>(),
+        ];
+        let board = self.board(layout, glyphs);
+        for (y, line) in board.lines().enumerate() {
+            lines.push(line.chars()
+                .enumerate()
+                .map(|(x, c)| {
+                    let fg = self.part_at_char(layout, x, y);
+                    let bg = self.background_at_char(layout, x, y);
+                    (
+                        c,
+                        fg.and_then(|part| theme.color(part)),
+                        bg.and_then(|part| theme.color(part)),
+                    )
+                })
+                .collect());
+        }
+        Frame::from_glyphs(lines)
+    }
+
+    // The board in `layout`. In the outline layout, an arrow is drawn on the
+    // edge of each head the snake is moving towards; sideways, that is the
+    // top edge, as the corners of the side edges are needed to tell the cells
+    // apart. In the compact layout, the arrow replaces the head, and in the
+    // half-block layout the whole character holding it.
+    pub fn board(&self, layout: Layout, glyphs: &Glyphs) -> String {
+        let board = match layout {
+            Layout::Outline => self.grid.render_with(glyphs),
+            Layout::Compact => self.grid.render_compact(glyphs),
+            Layout::HalfBlock => self.grid.render_half_blocks(glyphs),
+        };
+        let arrows = self.snakes.iter()
+            .filter(|snake| {
+                let head = snake.head();
+                self.grid[head.y*self.grid.width() + head.x] == Cell::Snake
+            })
+            .map(|snake| {
+                let head = snake.head();
+                let position = match (layout, snake.dir()) {
+                    (Layout::Outline, Direction::Down) => (2*head.x + 1, head.y + 1),
+                    (Layout::Outline, _) => (2*head.x + 1, head.y),
+                    (Layout::Compact, _) => (head.x + 1, head.y + 1),
+                    (Layout::HalfBlock, _) => (head.x + 1, head.y/2 + 1),
+                };
+                (position, glyphs.arrow(snake.dir()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut output = String::new();
+        for (y, line) in board.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let arrow = arrows.iter().find(|(position, _)| *position == (x, y));
+                output.push(arrow.map_or(c, |(_, arrow)| *arrow));
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+
+    // What the character at (x, y) of the board in `layout` belongs to, if
+    // anything, which gives it its color. In the half-block layout, that is
+    // the upper half if taken, as the lower one then is the background.
+    pub fn part_at_char(&self, layout: Layout, x: usize, y: usize) -> Option<Part> {
+        if self.on_border(layout, x, y) {
+            return Some(Part::Border);
+        }
+        match layout {
+            Layout::Outline => self.part_at(x/2, y),
+            Layout::Compact => self.part_of((y - 1)*self.grid.width() + x - 1),
+            Layout::HalfBlock => match self.halves_at_char(x, y) {
+                // The arrow takes the whole character
+                (Some(Part::Head), _) | (_, Some(Part::Head)) => Some(Part::Head),
+                (upper, lower) => upper.or(lower),
+            },
+        }
+    }
+
+    // What the background of the character at (x, y) of the board in
+    // `layout` belongs to, if anything: the lower half of a character with
+    // both halves taken.
+    pub fn background_at_char(&self, layout: Layout, x: usize, y: usize) -> Option<Part> {
+        if layout != Layout::HalfBlock || self.on_border(layout, x, y) {
+            return None;
+        }
+        match self.halves_at_char(x, y) {
+            (Some(Part::Head), _) | (_, Some(Part::Head)) => None,
+            (Some(_), lower) => lower,
+            (None, _) => None,
+        }
+    }
+
+    fn on_border(&self, layout: Layout, x: usize, y: usize) -> bool {
+        let (width, height) = self.grid.size_in_chars(layout);
+        layout != Layout::Outline
+            && (x == 0 || y == 0 || x == width - 1 || y == height - 1)
+    }
+
+    // What the upper and lower halves of the character at (x, y) inside the
+    // border of the half-block layout belong to
+    fn halves_at_char(&self, x: usize, y: usize) -> (Option<Part>, Option<Part>) {
+        let width = self.grid.width();
+        let upper = 2*(y - 1);
+        let lower = (upper + 1 < self.grid.height())
+            .then(|| self.part_of((upper + 1)*width + x - 1))
+            .flatten();
+        (self.part_of(upper*width + x - 1), lower)
+    }
+
+    // What the corner drawn at (x, y) by `Grid::render` belongs to, if
+    // anything.
+    pub fn part_at(&self, x: usize, y: usize) -> Option<Part> {
+        let grid = self.grid;
+        let part = grid.cells_at_corner(x, y).into_iter()
+            .filter_map(|i| self.part_of(i))
+            .max();
+
+        let on_border = x == 0 || y == 0 || x == grid.width() || y == grid.height();
+        match part {
+            None if on_border => Some(Part::Border),
+            part => part,
+        }
+    }
+
+    fn part_of(&self, i: usize) -> Option<Part> {
+        let grid = self.grid;
+        let position = Position { x: i % grid.width(), y: i / grid.width() };
+        match grid[i] {
+            Cell::Snake => self.snake_part(position),
+            Cell::Apple => Some(Part::Apple),
+            Cell::Wall => Some(Part::Wall),
+            Cell::Empty => None,
+        }
+    }
+
+    fn snake_part(&self, position: Position) -> Option<Part> {
+        // With a single snake, every snake cell is known to be part of it
+        let (player, snake) = match self.snakes {
+            [snake] => (0, snake),
+            snakes => snakes.iter()
+                .enumerate()
+                .find(|(_, snake)| snake.contains(position))?,
+        };
+        if position == snake.head() {
+            Some(Part::Head)
+        } else if player > 0 {
+            Some(Part::Rival)
+        } else if position == snake.tail() {
+            Some(Part::Tail)
+        } else {
+            Some(Part::Body)
+        }
+    }
+}
+
+pub trait Renderer {
+    // Size of the board and the header above it, in characters
+    fn board_size(&self, grid: &Grid) -> (usize, usize);
+
+    // Size of the output, in characters
+    fn size(&self) -> Result<(u16, u16), GameError>;
+
+    fn open(&mut self) -> Result<(), GameError>;
+
+    fn close(&mut self) -> Result<(), GameError>;
+
+    fn clear(&mut self) -> Result<(), GameError>;
+
+    fn render(&mut self, snapshot: &Snapshot) -> Result<(), GameError>;
+
+    // Draws `lines` centered on top of whatever is on the screen.
+    fn render_message(&mut self, lines: &[String]) -> Result<(), GameError>;
+}
+
+impl<R: Renderer + ?Sized> Renderer for Box<R> {
+    fn board_size(&self, grid: &Grid) -> (usize, usize) {
+        (**self).board_size(grid)
+    }
+
+    fn size(&self) -> Result<(u16, u16), GameError> {
+        (**self).size()
+    }
+
+    fn open(&mut self) -> Result<(), GameError> {
+        (**self).open()
+    }
+
+    fn close(&mut self) -> Result<(), GameError> {
+        (**self).close()
+    }
+
+    fn clear(&mut self) -> Result<(), GameError> {
+        (**self).clear()
+    }
+
+    fn render(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        (**self).render(snapshot)
+    }
+
+    fn render_message(&mut self, lines: &[String]) -> Result<(), GameError> {
+        (**self).render_message(lines)
+    }
+}
+
+// Draws the board with box-drawing characters on the alternate screen,
+// printing only what changed since the previous frame.
+pub struct TerminalRenderer {
+    theme: &'static Theme,
+    glyphs: &'static Glyphs,
+    layout: Layout,
+    // Present between `open` and `close`
+    screen: Option<Screen>,
+    frame: DiffRenderer,
+    // Whether the frame on the screen was drawn dimmed
+    dimmed: bool,
+}
+
+impl TerminalRenderer {
+    pub fn new(theme: &'static Theme, glyphs: &'static Glyphs, layout: Layout) -> Self {
+        Self {
+            theme,
+            glyphs,
+            layout,
+            screen: None,
+            frame: DiffRenderer::new(),
+            dimmed: false,
+        }
+    }
+
+    fn screen(&mut self) -> Result<&mut Screen, GameError> {
+        match self.screen.as_mut() {
+            Some(screen) => Ok(screen),
+            None => Err(GameError::PrintText),
+        }
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    fn board_size(&self, grid: &Grid) -> (usize, usize) {
+        let (width, height) = grid.size_in_chars(self.layout);
+        (width, height + HEADER_HEIGHT)
+    }
+
+    fn size(&self) -> Result<(u16, u16), GameError> {
+        terminal_size()
+    }
+
+    fn open(&mut self) -> Result<(), GameError> {
+        let stdout = match stdout().into_raw_mode() {
+            Ok(stdout) => stdout,
+            Err(_) => return Err(GameError::SwitchIntoRawMode),
+        };
+        let mut screen = match stdout.into_alternate_screen() {
+            Ok(screen) => screen,
+            Err(_) => return Err(GameError::SwitchIntoAlternateScreen),
+        };
+
+        if write!(screen, "{}", cursor::Hide).is_err() {
+            return Err(GameError::HideCursor);
+        }
+        if write!(screen, "{}", ENABLE_FOCUS_EVENTS).is_err() {
+            return Err(GameError::PrintText);
+        }
+
+        self.screen = Some(screen);
+        self.frame.invalidate();
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), GameError> {
+        // Dropping the screen leaves the alternate screen and raw mode
+        let mut screen = match self.screen.take() {
+            Some(screen) => screen,
+            None => return Ok(()),
+        };
+        if write!(screen, "{}", DISABLE_FOCUS_EVENTS).is_err() {
+            return Err(GameError::PrintText);
+        }
+        if write!(screen, "{}", cursor::Show).is_err() {
+            return Err(GameError::ShowCursor);
+        }
+
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), GameError> {
+        self.frame.invalidate();
+        if write!(self.screen()?, "{}", clear::All).is_err() {
+            return Err(GameError::ClearScreen);
+        }
+
+        Ok(())
+    }
+
+    fn render(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        let (col_count, row_count) = self.size()?;
+        let (width, height) = self.board_size(snapshot.grid);
+        let top_margin = row_count.saturating_sub(height as u16)/2 + 1;
+        let left_margin = (col_count as usize).saturating_sub(width)/2;
+
+        let frame = snapshot.frame(self.layout, self.glyphs, self.theme);
+
+        // Characters keep the style they were printed with
+        if snapshot.dimmed != self.dimmed {
+            self.dimmed = snapshot.dimmed;
+            self.frame.invalidate();
+        }
+        let prefix = if snapshot.dimmed {
+            style::Faint.to_string()
+        } else {
+            String::new()
+        };
+        let origin = (left_margin as u16 + 1, top_margin);
+
+        let screen = match self.screen.as_mut() {
+            Some(screen) => screen,
+            None => return Err(GameError::PrintText),
+        };
+        if write!(screen, "{}", prefix).is_err() {
+            return Err(GameError::PrintText);
+        }
+        if self.frame.draw(screen, frame, origin).is_err() {
+            return Err(GameError::PrintText);
+        }
+        if write!(screen, "{}", style::Reset).is_err() {
+            return Err(GameError::PrintText);
+        }
+
+        if screen.flush().is_err() {
+            return Err(GameError::FlushScreen);
+        }
+
+        Ok(())
+    }
+
+    fn render_message(&mut self, lines: &[String]) -> Result<(), GameError> {
+        let (col_count, row_count) = self.size()?;
+        let top_margin = (row_count/2).saturating_sub(1).max(1);
+        self.frame.invalidate();
+
+        let screen = self.screen()?;
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.chars()
+                .take(col_count as usize)
+                .collect::<String>();
+            let left_margin =
+                (col_count as usize - line.chars().count())/2 + 1;
+            let goto = cursor::Goto(left_margin as u16, top_margin + i as u16);
+            if write!(screen, "{}{}", goto, line).is_err() {
+                return Err(GameError::SetCursorPos);
+            }
+        }
+
+        if screen.flush().is_err() {
+            return Err(GameError::FlushScreen);
+        }
+
+        Ok(())
+    }
+}
+
+// Prints each frame below the previous one using only ASCII characters, for
+// terminals that cannot move the cursor or lack box-drawing glyphs.
+#[derive(Default)]
+pub struct AsciiRenderer {
+    // Present between `open` and `close`
+    stdout: Option<RawTerminal<Stdout>>,
+    // The last frame printed, which is not printed again
+    previous: String,
+}
+
+impl AsciiRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn print(&mut self, text: &str) -> Result<(), GameError> {
+        let stdout = match self.stdout.as_mut() {
+            Some(stdout) => stdout,
+            None => return Err(GameError::PrintText),
+        };
+        if write!(stdout, "{}", text).is_err() {
+            return Err(GameError::PrintText);
+        }
+        if stdout.flush().is_err() {
+            return Err(GameError::FlushScreen);
+        }
+
+        Ok(())
+    }
+}
+
+impl Renderer for AsciiRenderer {
+    fn board_size(&self, grid: &Grid) -> (usize, usize) {
+        (2*grid.width() + 2, grid.height() + 2 + HEADER_HEIGHT)
+    }
+
+    fn size(&self) -> Result<(u16, u16), GameError> {
+        Ok(termion::terminal_size().unwrap_or(FALLBACK_SIZE))
+    }
+
+    fn open(&mut self) -> Result<(), GameError> {
+        match stdout().into_raw_mode() {
+            Ok(stdout) => self.stdout = Some(stdout),
+            Err(_) => return Err(GameError::SwitchIntoRawMode),
+        }
+        self.previous.clear();
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), GameError> {
+        if self.stdout.is_some() {
+            self.print("\r\n")?;
+        }
+        self.stdout = None;
+
+        Ok(())
+    }
+
+    // There is nothing to clear, but the next frame is printed in any case.
+    fn clear(&mut self) -> Result<(), GameError> {
+        self.previous.clear();
+
+        Ok(())
+    }
+
+    fn render(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        let text = format!("{}\n{}", snapshot.header(), render_ascii(snapshot.grid, snapshot.snakes));
+        if text == self.previous {
+            return Ok(());
+        }
+
+        self.print(&format!("\r\n{}", text.replace('\n', "\r\n")))?;
+        self.previous = text;
+
+        Ok(())
+    }
+
+    fn render_message(&mut self, lines: &[String]) -> Result<(), GameError> {
+        self.previous.clear();
+        let text = lines.iter()
+            .map(|line| line.trim())
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        self.print(&format!("{}\r\n", text))
+    }
+}
+
+// The second player's snake is drawn with braces instead of brackets.
+fn render_ascii(grid: &Grid, snakes: &[Snake]) -> String {
+    let (horizontal, vertical) = match grid.walls() {
+        Walls::Solid => ("--", '|'),
+        Walls::Open => ("..", ':'),
+    };
+    let border = format!("+{}+\n", horizontal.repeat(grid.width()));
+
+    let mut output = border.clone();
+    for y in 0..grid.height() {
+        output.push(vertical);
+        for x in 0..grid.width() {
+            let position = Position { x, y };
+            let cell = grid[y*grid.width() + x];
+            let snake = match cell {
+                Cell::Snake => snakes.iter()
+                    .position(|snake| snakes.len() == 1 || snake.contains(position)),
+                _ => None,
+            };
+            output.push_str(match (cell, snake) {
+                (Cell::Empty, _) => "  ",
+                (Cell::Apple, _) => "()",
+                (Cell::Snake, Some(i)) if snakes[i].head() == position => {
+                    match snakes[i].dir() {
+                        Direction::Up => "/\\",
+                        Direction::Down => "\\/",
+                        Direction::Left => "<]",
+                        Direction::Right => "[>",
+                    }
+                },
+                (Cell::Snake, Some(i)) if i > 0 => "{}",
+                (Cell::Snake, _) => "[]",
+                (Cell::Wall, _) => "##",
+            });
+        }
+        output.push(vertical);
+        output.push('\n');
+    }
+    output.push_str(&border);
+
+    output
+}
+
+// Keeps the frames in memory instead of drawing them, for tests.
+pub struct MemoryRenderer {
+    size: (u16, u16),
+    // The header and the board of each frame, as drawn by `TerminalRenderer`
+    pub frames: Vec<String>,
+    pub messages: Vec<Vec<String>>,
+}
+
+impl MemoryRenderer {
+    pub fn new(size: (u16, u16)) -> Self {
+        Self {
+            size,
+            frames: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+}
+
+impl Renderer for MemoryRenderer {
+    fn board_size(&self, grid: &Grid) -> (usize, usize) {
+        (grid.width_in_chars(), grid.height_in_chars() + HEADER_HEIGHT)
+    }
+
+    fn size(&self) -> Result<(u16, u16), GameError> {
+        Ok(self.size)
+    }
+
+    fn open(&mut self) -> Result<(), GameError> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), GameError> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), GameError> {
+        Ok(())
+    }
+
+    fn render(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        let board = snapshot.board(Layout::Outline, Glyphs::default_set());
+        self.frames.push(format!("{}\n{}", snapshot.header(), board));
+
+        Ok(())
+    }
+
+    fn render_message(&mut self, lines: &[String]) -> Result<(), GameError> {
+        self.messages.push(lines.to_vec());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq as pretty_assert_eq;
+
+    use crate::difficulty::Difficulty;
+    use crate::direction::Direction;
+    use crate::engine::Engine;
+    use crate::level::Level;
+
+    fn snapshot(engine: &Engine) -> Snapshot<'_> {
+        Snapshot {
+            grid: engine.grid(),
+            snakes: engine.snakes(),
+            scores: engine.scores(),
+            speed: Speed {
+                difficulty: Difficulty::Hard,
+                progressive: false,
+            },
+            status: "  Replay 0/9",
+            dimmed: false,
+        }
+    }
+
+    fn board(engine: &Engine, layout: Layout) -> String {
+        snapshot(engine).board(layout, Glyphs::default_set())
+    }
+
+    #[test]
+    fn test_header() {
+        let engine = Engine::new(&Level::empty(3, 2), Walls::Solid, 0);
+
+        assert_eq!(snapshot(&engine).header(), " Score: 0  Speed: 1 (hard)  Replay 0/9");
+    }
+
+    // The second snake eats on the first tick and has grown by the second
+    fn two_player_engine() -> Engine {
+        let level = Level::parse("\
+......
+....A.
+^.....
+").unwrap();
+        let spawns = [
+            (Position { x: 1, y: 1 }, Direction::Right),
+            (Position { x: 4, y: 2 }, Direction::Up),
+        ];
+        let mut engine = Engine::with_spawns(&level, Walls::Solid, 0, &spawns);
+        engine.tick_players(&[&[], &[]]);
+        engine.tick_players(&[&[], &[]]);
+        engine
+    }
+
+    #[test]
+    fn test_header_when_there_are_two_players_then_shows_both_scores() {
+        let engine = two_player_engine();
+
+        assert_eq!(
+            snapshot(&engine).header(),
+            " P1: 0  P2: 1  Speed: 1 (hard)  Replay 0/9",
+        );
+    }
+
+    #[test]
+    fn test_part_at_char_tells_the_snakes_apart() {
+        let engine = two_player_engine();
+        let snapshot = snapshot(&engine);
+
+        assert_eq!(snapshot.part_at_char(Layout::Compact, 4, 2), Some(Part::Head));
+        assert_eq!(snapshot.part_at_char(Layout::Compact, 5, 1), Some(Part::Head));
+        assert_eq!(snapshot.part_at_char(Layout::Compact, 5, 2), Some(Part::Rival));
+    }
+
+    #[test]
+    fn test_render_ascii_when_there_are_two_players() {
+        let engine = two_player_engine();
+
+        pretty_assert_eq!(render_ascii(engine.grid(), engine.snakes()), "\
++------------+
+|        /\\  |
+|      [>{}  |
+|    ()      |
++------------+
+");
+    }
+
+    #[test]
+    fn test_board_when_snake_moves_sideways_then_marks_top_edge_of_head() {
+        let level = Level::parse("\
+....
+.>..
+").unwrap();
+        let mut engine = Engine::new(&level, Walls::Solid, 0);
+        engine.tick(&[]);
+
+        pretty_assert_eq!(board(&engine, Layout::Outline), "\
+┌───────┐
+│ ┌─┬▸┐ │
+└─┴─┴─┴─┘
+");
+    }
+
+    #[test]
+    fn test_board_when_snake_moves_down_then_marks_bottom_edge_of_head() {
+        let level = Level::parse("\
+.v..
+....
+....
+").unwrap();
+        let mut engine = Engine::new(&level, Walls::Solid, 0);
+        engine.tick(&[]);
+
+        pretty_assert_eq!(board(&engine, Layout::Outline), "\
+┌───────┐
+│ ┌─┐   │
+│ ├▾┤   │
+└─┴─┴───┘
+");
+    }
+
+    #[test]
+    fn test_board_in_compact_layout_then_replaces_head_with_arrow() {
+        let level = Level::parse("\
+....
+.>..
+").unwrap();
+        let mut engine = Engine::new(&level, Walls::Solid, 0);
+        engine.tick(&[]);
+
+        pretty_assert_eq!(board(&engine, Layout::Compact), "\
+┌────┐
+│    │
+│ ●▸ │
+└────┘
+");
+    }
+
+    #[test]
+    fn test_board_in_half_block_layout_then_replaces_head_character_with_arrow() {
+        let level = Level::parse("\
+....
+.>..
+").unwrap();
+        let mut engine = Engine::new(&level, Walls::Solid, 0);
+        engine.tick(&[]);
+
+        pretty_assert_eq!(board(&engine, Layout::HalfBlock), "\
+┌────┐
+│ ▄▸ │
+└────┘
+");
+    }
+
+    #[test]
+    fn test_background_at_char_when_both_halves_are_taken_then_is_the_lower_one() {
+        let level = Level::parse("\
+A...
+#^..
+").unwrap();
+        let engine = Engine::new(&level, Walls::Solid, 0);
+        let snapshot = snapshot(&engine);
+
+        assert_eq!(snapshot.part_at_char(Layout::HalfBlock, 1, 1), Some(Part::Apple));
+        assert_eq!(snapshot.background_at_char(Layout::HalfBlock, 1, 1), Some(Part::Wall));
+        assert_eq!(snapshot.part_at_char(Layout::HalfBlock, 2, 1), Some(Part::Head));
+        assert_eq!(snapshot.background_at_char(Layout::HalfBlock, 2, 1), None);
+        assert_eq!(snapshot.background_at_char(Layout::Compact, 1, 1), None);
+    }
+
+    #[test]
+    fn test_part_at_char() {
+        // A...
+        // .^..
+        // ....
+        let level = Level::parse("\
+A...
+.^..
+....
+").unwrap();
+        let engine = Engine::new(&level, Walls::Solid, 0);
+        let snapshot = snapshot(&engine);
+
+        assert_eq!(snapshot.part_at_char(Layout::Outline, 3, 1), Some(Part::Head));
+        assert_eq!(snapshot.part_at_char(Layout::Compact, 0, 2), Some(Part::Border));
+        assert_eq!(snapshot.part_at_char(Layout::Compact, 1, 1), Some(Part::Apple));
+        assert_eq!(snapshot.part_at_char(Layout::Compact, 2, 2), Some(Part::Head));
+        assert_eq!(snapshot.part_at_char(Layout::Compact, 2, 1), None);
+        assert_eq!(snapshot.part_at_char(Layout::HalfBlock, 2, 1), Some(Part::Head));
+        assert_eq!(snapshot.part_at_char(Layout::HalfBlock, 1, 1), Some(Part::Apple));
+        assert_eq!(snapshot.part_at_char(Layout::HalfBlock, 2, 2), None);
+        assert_eq!(snapshot.part_at_char(Layout::HalfBlock, 2, 3), Some(Part::Border));
+    }
+
+    #[test]
+    fn test_part_at() {
+        // .BH..
+        // .T...
+        // ...#A
+        let mut grid = Grid::new(5, 3);
+        grid[5 + 1] = Cell::Apple;
+        grid[1] = Cell::Apple;
+        grid[2*5 + 3] = Cell::Wall;
+        grid[2*5 + 4] = Cell::Apple;
+        let mut snake = Snake::new(Position { x: 1, y: 2 }, Direction::Up);
+        snake.update(&mut grid);
+        snake.update(&mut grid);
+        snake.set_dir(Direction::Right);
+        snake.update(&mut grid);
+        let snapshot = Snapshot {
+            grid: &grid,
+            snakes: &[snake],
+            scores: &[0],
+            speed: Speed::default(),
+            status: "",
+            dimmed: false,
+        };
+
+        assert_eq!(snapshot.part_at(0, 0), Some(Part::Border));
+        assert_eq!(snapshot.part_at(1, 0), Some(Part::Body));
+        assert_eq!(snapshot.part_at(3, 1), Some(Part::Head));
+        assert_eq!(snapshot.part_at(2, 1), Some(Part::Head));
+        assert_eq!(snapshot.part_at(1, 1), Some(Part::Tail));
+        assert_eq!(snapshot.part_at(3, 2), Some(Part::Wall));
+        assert_eq!(snapshot.part_at(4, 3), Some(Part::Apple));
+        assert_eq!(snapshot.part_at(2, 3), Some(Part::Border));
+        assert_eq!(snapshot.part_at(4, 1), None);
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let level = Level::parse("\
+#A..
+.^..
+").unwrap();
+        let engine = Engine::new(&level, Walls::Solid, 0);
+
+        pretty_assert_eq!(render_ascii(engine.grid(), engine.snakes()), "\
++--------+
+|##()    |
+|  /\\    |
++--------+
+");
+    }
+
+    #[test]
+    fn test_render_ascii_when_walls_are_open() {
+        let level = Level::parse("\
+A.
+^.
+").unwrap();
+        let engine = Engine::new(&level, Walls::Open, 0);
+
+        pretty_assert_eq!(render_ascii(engine.grid(), engine.snakes()), "\
++....+
+:()  :
+:/\\  :
++....+
+");
+    }
+
+    #[test]
+    fn test_memory_renderer_captures_frames() {
+        let level = Level::parse("\
+A.
+^.
+").unwrap();
+        let engine = Engine::new(&level, Walls::Solid, 0);
+        let mut renderer = MemoryRenderer::new((80, 24));
+
+        renderer.render(&snapshot(&engine)).unwrap();
+        renderer.render_message(&[String::from("Hello")]).unwrap();
+
+        assert_eq!(
+            renderer.frames,
+            [format!("{}\n{}", snapshot(&engine).header(), board(&engine, Layout::Outline))],
+        );
+        assert_eq!(renderer.messages, [[String::from("Hello")]]);
+    }
+}
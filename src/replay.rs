@@ -0,0 +1,397 @@
+use std::fs;
+use std::path::Path;
+
+use crate::difficulty::{ Difficulty, Speed };
+use crate::direction::Direction;
+use crate::grid::Walls;
+use crate::level::{ Level, LevelError };
+
+// Replay file format:
+//
+//   snake-replay 3
+//   seed 1234
+//   walls solid
+//   difficulty normal
+//   progressive off
+//   ticks 57
+//   level 4
+//   ....
+//   .^..
+//   ....
+//   A...
+//   12 L
+//   20 DR
+//
+// The header is followed by the level (see `level.rs`) and by one line per
+// tick in which the player changed direction: the zero-based tick number and
+// the directions, in order, as `L`, `D`, `U` or `R`.
+
+const MAGIC: &str = "snake-replay";
+// Version 2: turns are queued and applied one per tick
+// Version 3: the speed is recorded, version 2 replays play at normal speed
+const VERSION: u32 = 3;
+const MIN_VERSION: u32 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    ReadFile,
+    WriteFile,
+    UnsupportedVersion,
+    Malformed { line: usize },
+    Level(LevelError),
+}
+
+pub struct Replay {
+    pub level: Level,
+    pub walls: Walls,
+    pub seed: u64,
+    pub speed: Speed,
+    pub ticks: usize,
+    // Inputs of the ticks that had any, ordered by tick
+    pub inputs: Vec<(usize, Vec<Direction>)>,
+}
+
+impl Replay {
+    pub fn new(level: Level, walls: Walls, seed: u64, speed: Speed) -> Self {
+        Self {
+            level,
+            walls,
+            seed,
+            speed,
+            ticks: 0,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, inputs: &[Direction]) {
+        if !inputs.is_empty() {
+            self.inputs.push((self.ticks, inputs.to_vec()));
+        }
+        self.ticks += 1;
+    }
+
+    pub fn inputs_at(&self, tick: usize) -> &[Direction] {
+        match self.inputs.binary_search_by_key(&tick, |(t, _)| *t) {
+            Ok(i) => &self.inputs[i].1,
+            Err(_) => &[],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(_) => Err(ReplayError::ReadFile),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(dir) = path.parent() {
+            if fs::create_dir_all(dir).is_err() {
+                return Err(ReplayError::WriteFile);
+            }
+        }
+        match fs::write(path, self.serialize()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ReplayError::WriteFile),
+        }
+    }
+
+    pub fn serialize(&self) -> String {
+        let walls = match self.walls {
+            Walls::Solid => "solid",
+            Walls::Open => "open",
+        };
+        let mut output = format!(
+            "{} {}\nseed {}\nwalls {}\ndifficulty {}\nprogressive {}\n\
+             ticks {}\nlevel {}\n{}",
+            MAGIC,
+            VERSION,
+            self.seed,
+            walls,
+            self.speed.difficulty.name(),
+            if self.speed.progressive { "on" } else { "off" },
+            self.ticks,
+            self.level.height,
+            self.level.serialize(),
+        );
+
+        for (tick, dirs) in self.inputs.iter() {
+            let dirs = dirs.iter()
+                .map(|dir| match dir {
+                    Direction::Left => 'L',
+                    Direction::Down => 'D',
+                    Direction::Up => 'U',
+                    Direction::Right => 'R',
+                })
+                .collect::<String>();
+            output.push_str(&format!("{} {}\n", tick, dirs));
+        }
+
+        output
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ReplayError> {
+        let lines = text.lines().collect::<Vec<_>>();
+
+        let version = match header(&lines, 0, MAGIC)?.parse::<u32>() {
+            Ok(version) if (MIN_VERSION..=VERSION).contains(&version) => version,
+            _ => return Err(ReplayError::UnsupportedVersion),
+        };
+        let seed = parse_number(header(&lines, 1, "seed")?, 2)?;
+        let walls = match header(&lines, 2, "walls")? {
+            "solid" => Walls::Solid,
+            "open" => Walls::Open,
+            _ => return Err(ReplayError::Malformed { line: 3 }),
+        };
+
+        let mut index = 3;
+        let mut speed = Speed::default();
+        if version >= 3 {
+            let name = header(&lines, index, "difficulty")?;
+            speed.difficulty = match Difficulty::from_name(name) {
+                Some(difficulty) => difficulty,
+                None => return Err(ReplayError::Malformed { line: index + 1 }),
+            };
+            speed.progressive = match header(&lines, index + 1, "progressive")? {
+                "on" => true,
+                "off" => false,
+                _ => return Err(ReplayError::Malformed { line: index + 2 }),
+            };
+            index += 2;
+        }
+
+        let ticks = parse_number(header(&lines, index, "ticks")?, index + 1)?;
+        let height = parse_number(header(&lines, index + 1, "level")?, index + 2)?;
+
+        let level_start = index + 2;
+        if height > lines.len().saturating_sub(level_start) as u64 {
+            return Err(ReplayError::Malformed { line: lines.len() + 1 });
+        }
+        let level_end = level_start + height as usize;
+        let level = match Level::parse(&lines[level_start..level_end].join("\n")) {
+            Ok(level) => level,
+            Err(e) => return Err(ReplayError::Level(e)),
+        };
+
+        let mut inputs: Vec<(usize, Vec<Direction>)> = Vec::new();
+        for (i, line) in lines.iter().enumerate().skip(level_end) {
+            let malformed = ReplayError::Malformed { line: i + 1 };
+            let (tick, dirs) = match line.split_once(' ') {
+                Some(parts) => parts,
+                None => return Err(malformed),
+            };
+            let tick = parse_number(tick, i + 1)? as usize;
+            if tick >= ticks as usize
+                || inputs.last().is_some_and(|(last, _)| *last >= tick)
+            {
+                return Err(malformed);
+            }
+            let dirs = dirs.chars()
+                .map(|symbol| match symbol {
+                    'L' => Some(Direction::Left),
+                    'D' => Some(Direction::Down),
+                    'U' => Some(Direction::Up),
+                    'R' => Some(Direction::Right),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            match dirs {
+                Some(dirs) if !dirs.is_empty() => inputs.push((tick, dirs)),
+                _ => return Err(malformed),
+            }
+        }
+
+        Ok(Self {
+            level,
+            walls,
+            seed,
+            speed,
+            ticks: ticks as usize,
+            inputs,
+        })
+    }
+}
+
+fn header<'a>(
+    lines: &[&'a str],
+    index: usize,
+    key: &str,
+) -> Result<&'a str, ReplayError> {
+    let malformed = ReplayError::Malformed { line: index + 1 };
+    match lines.get(index).and_then(|line| line.split_once(' ')) {
+        Some((k, value)) if k == key => Ok(value),
+        _ => Err(malformed),
+    }
+}
+
+fn parse_number(value: &str, line: usize) -> Result<u64, ReplayError> {
+    match value.parse::<u64>() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(ReplayError::Malformed { line }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+
+    fn recorded() -> Replay {
+        let level = Level::parse("\
+#...
+.>..
+").unwrap();
+        let speed = Speed {
+            difficulty: Difficulty::Hard,
+            progressive: true,
+        };
+        let mut replay = Replay::new(level, Walls::Open, 42, speed);
+        replay.record(&[]);
+        replay.record(&[Direction::Down]);
+        replay.record(&[]);
+        replay.record(&[Direction::Left, Direction::Up]);
+        replay.record(&[]);
+        replay
+    }
+
+    #[test]
+    fn test_serialize() {
+        let expected = "\
+snake-replay 3
+seed 42
+walls open
+difficulty hard
+progressive on
+ticks 5
+level 2
+#...
+.>..
+1 D
+3 LU
+";
+
+        assert_eq!(recorded().serialize(), expected);
+    }
+
+    #[test]
+    fn test_parse_is_inverse_of_serialize() {
+        let replay = Replay::parse(&recorded().serialize()).unwrap();
+
+        assert_eq!(replay.serialize(), recorded().serialize());
+        assert_eq!(replay.seed, 42);
+        assert_eq!(replay.walls, Walls::Open);
+        assert_eq!(replay.speed.difficulty, Difficulty::Hard);
+        assert!(replay.speed.progressive);
+        assert_eq!(replay.ticks, 5);
+        assert_eq!(replay.inputs_at(1), &[Direction::Down]);
+        assert_eq!(replay.inputs_at(2), &[]);
+        assert_eq!(replay.inputs_at(3), &[Direction::Left, Direction::Up]);
+    }
+
+    #[test]
+    fn test_playing_back_reproduces_the_game() {
+        let level = Level::empty(10, 6);
+        let mut engine = Engine::new(&level, Walls::Open, 7);
+        let mut replay = Replay::new(level, Walls::Open, 7, Speed::default());
+        let turns = [Direction::Left, Direction::Down, Direction::Right];
+        for tick in 0..60 {
+            let inputs = if tick % 4 == 0 {
+                vec![turns[tick/4 % turns.len()]]
+            } else {
+                vec![]
+            };
+            replay.record(&inputs);
+            engine.tick(&inputs);
+        }
+
+        let replay = Replay::parse(&replay.serialize()).unwrap();
+        let mut played_back = Engine::new(&replay.level, replay.walls, replay.seed);
+        for tick in 0..replay.ticks {
+            played_back.tick(replay.inputs_at(tick));
+        }
+
+        assert_eq!(played_back.grid().render(), engine.grid().render());
+        assert_eq!(played_back.points(), engine.points());
+    }
+
+    #[test]
+    fn test_parse_when_version_is_2_then_uses_normal_speed() {
+        let text = "\
+snake-replay 2
+seed 42
+walls solid
+ticks 5
+level 2
+....
+.^..
+1 D
+";
+        let replay = Replay::parse(text).unwrap();
+
+        assert_eq!(replay.speed, Speed::default());
+        assert_eq!(replay.ticks, 5);
+        assert_eq!(replay.inputs_at(1), &[Direction::Down]);
+    }
+
+    #[test]
+    fn test_parse_when_version_is_unknown_then_fails() {
+        let text = recorded().serialize().replace("snake-replay 3", "snake-replay 1");
+
+        assert_eq!(
+            Replay::parse(&text).err(),
+            Some(ReplayError::UnsupportedVersion),
+        );
+    }
+
+    #[test]
+    fn test_parse_when_inputs_are_malformed_then_fails() {
+        let text = recorded().serialize();
+
+        for (bad, line) in [("1 X", 10), ("7 D", 10), ("1 ", 10), ("x D", 10)] {
+            let bad_text = text.replace("1 D", bad);
+            assert_eq!(
+                Replay::parse(&bad_text).err(),
+                Some(ReplayError::Malformed { line }),
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_when_level_is_truncated_then_fails() {
+        let text = "\
+snake-replay 3
+seed 42
+walls solid
+difficulty normal
+progressive off
+ticks 5
+level 3
+....
+.^..
+";
+
+        assert_eq!(
+            Replay::parse(text).err(),
+            Some(ReplayError::Malformed { line: 10 }),
+        );
+    }
+
+    #[test]
+    fn test_parse_when_level_height_is_huge_then_fails() {
+        let text = "\
+snake-replay 3
+seed 42
+walls solid
+difficulty normal
+progressive off
+ticks 5
+level 18446744073709551615
+....
+";
+
+        assert_eq!(
+            Replay::parse(text).err(),
+            Some(ReplayError::Malformed { line: 9 }),
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// High-score file format, one entry per line:
+//
+//   <mode>\t<width>x<height>\t<points>\t<name>
+//
+// Lines that cannot be parsed are skipped, so a damaged file only loses the
+// damaged entries.
+
+pub const MAX_ENTRIES: usize = 10;
+const MAX_NAME_LENGTH: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum ScoresError {
+    WriteFile,
+}
+
+// Scores are only compared within the same category.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Category {
+    pub mode: String,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}x{}", self.mode, self.width, self.height)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub points: usize,
+}
+
+#[derive(Default)]
+pub struct Scores {
+    tables: BTreeMap<Category, Vec<Entry>>,
+}
+
+impl Scores {
+    // A missing or unreadable file is treated as an empty table.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ScoresError> {
+        if let Some(dir) = path.parent() {
+            if fs::create_dir_all(dir).is_err() {
+                return Err(ScoresError::WriteFile);
+            }
+        }
+        // Writing to a temporary file first keeps the old table intact if
+        // the game is interrupted halfway through
+        let tmp_path = path.with_extension("tmp");
+        if fs::write(&tmp_path, self.serialize()).is_err() {
+            return Err(ScoresError::WriteFile);
+        }
+        match fs::rename(&tmp_path, path) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ScoresError::WriteFile),
+        }
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut scores = Self::default();
+
+        for line in text.lines() {
+            if let Some((category, entry)) = parse_line(line) {
+                scores.add(&category, entry);
+            }
+        }
+
+        scores
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut output = String::new();
+
+        for (category, entries) in self.tables.iter() {
+            for entry in entries {
+                output.push_str(&format!(
+                    "{}\t{}x{}\t{}\t{}\n",
+                    category.mode,
+                    category.width,
+                    category.height,
+                    entry.points,
+                    entry.name,
+                ));
+            }
+        }
+
+        output
+    }
+
+    pub fn qualifies(&self, category: &Category, points: usize) -> bool {
+        let entries = self.top(category);
+        points > 0 && (
+            entries.len() < MAX_ENTRIES
+            || entries.last().is_some_and(|last| points > last.points)
+        )
+    }
+
+    // Adds the entry and returns its zero-based rank, or `None` if it did
+    // not make it into the table.
+    pub fn add(&mut self, category: &Category, mut entry: Entry) -> Option<usize> {
+        entry.name = sanitize_name(&entry.name);
+        let entries = self.tables.entry(category.clone()).or_default();
+        // Earlier entries win ties
+        let rank = entries.iter()
+            .position(|other| entry.points > other.points)
+            .unwrap_or(entries.len());
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        entries.insert(rank, entry);
+        entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    pub fn top(&self, category: &Category) -> &[Entry] {
+        match self.tables.get(category) {
+            Some(entries) => entries,
+            None => &[],
+        }
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = &Category> {
+        self.tables.keys()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(Category, Entry)> {
+    let mut fields = line.split('\t');
+    let mode = fields.next()?;
+    let (width, height) = fields.next()?.split_once('x')?;
+    let points = fields.next()?.parse().ok()?;
+    let name = fields.next()?;
+    if mode.is_empty() || fields.next().is_some() {
+        return None;
+    }
+
+    let category = Category {
+        mode: mode.to_string(),
+        width: width.parse().ok()?,
+        height: height.parse().ok()?,
+    };
+    let entry = Entry {
+        name: name.to_string(),
+        points,
+    };
+    Some((category, entry))
+}
+
+fn sanitize_name(name: &str) -> String {
+    let name = name.chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    let name = name.trim()
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+    if name.is_empty() {
+        return String::from("anonymous");
+    }
+    name.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category() -> Category {
+        Category {
+            mode: String::from("classic"),
+            width: 36,
+            height: 20,
+        }
+    }
+
+    fn entry(name: &str, points: usize) -> Entry {
+        Entry {
+            name: name.to_string(),
+            points,
+        }
+    }
+
+    #[test]
+    fn test_add_keeps_entries_sorted() {
+        let mut scores = Scores::default();
+
+        assert_eq!(scores.add(&category(), entry("a", 5)), Some(0));
+        assert_eq!(scores.add(&category(), entry("b", 9)), Some(0));
+        assert_eq!(scores.add(&category(), entry("c", 5)), Some(2));
+
+        let names = scores.top(&category()).iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_add_keeps_only_the_best_entries() {
+        let mut scores = Scores::default();
+        for points in 1..=MAX_ENTRIES {
+            scores.add(&category(), entry("a", points));
+        }
+
+        assert!(!scores.qualifies(&category(), 1));
+        assert_eq!(scores.add(&category(), entry("b", 1)), None);
+        assert!(scores.qualifies(&category(), 2));
+        assert_eq!(scores.add(&category(), entry("b", 2)), Some(MAX_ENTRIES - 1));
+        assert_eq!(scores.top(&category()).len(), MAX_ENTRIES);
+        assert_eq!(scores.top(&category()).last(), Some(&entry("b", 2)));
+    }
+
+    #[test]
+    fn test_categories_are_independent() {
+        let mut scores = Scores::default();
+        let small = Category { width: 10, ..category() };
+        scores.add(&category(), entry("a", 5));
+
+        assert!(scores.top(&small).is_empty());
+        assert!(!scores.qualifies(&small, 0));
+        assert!(scores.qualifies(&small, 1));
+    }
+
+    #[test]
+    fn test_parse_is_inverse_of_serialize() {
+        let mut scores = Scores::default();
+        scores.add(&category(), entry("alice", 12));
+        scores.add(&category(), entry("bob", 30));
+        scores.add(&Category { mode: String::from("wrap"), ..category() }, entry("carol", 7));
+        let text = scores.serialize();
+
+        assert_eq!(text, "\
+classic\t36x20\t30\tbob
+classic\t36x20\t12\talice
+wrap\t36x20\t7\tcarol
+");
+        assert_eq!(Scores::parse(&text).serialize(), text);
+    }
+
+    #[test]
+    fn test_parse_skips_corrupted_lines() {
+        let scores = Scores::parse("\
+classic\t36x20\t30\tbob
+classic\t36x20\tlots\tmallory
+garbage
+classic\t36-20\t3\teve
+\u{0}\u{0}\u{0}
+classic\t36x20\t12\talice
+");
+
+        assert_eq!(scores.top(&category()), &[entry("bob", 30), entry("alice", 12)]);
+    }
+
+    #[test]
+    fn test_add_sanitizes_names() {
+        let mut scores = Scores::default();
+        scores.add(&category(), entry("  a\tvery\nlong name indeed  ", 3));
+        scores.add(&category(), entry("", 2));
+
+        assert_eq!(scores.top(&category())[0].name, "averylong name i");
+        assert_eq!(scores.top(&category())[1].name, "anonymous");
+    }
+}
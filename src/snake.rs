@@ -1,9 +1,11 @@
-use std::collections::LinkedList;
+use std::collections::{ LinkedList, VecDeque };
 
 use crate::direction::{ Direction, are_opposite };
-use crate::grid::{ Cell, Grid };
+use crate::grid::{ Cell, Grid, Walls };
 use crate::position::Position;
 
+const MAX_QUEUED_TURNS: usize = 3;
+
 #[derive(Debug, PartialEq)]
 pub enum Status {
     Ate,
@@ -13,27 +15,42 @@ pub enum Status {
 
 pub struct Snake {
     dir: Direction,
+    // Turns waiting to be applied, one per update
+    turns: VecDeque<Direction>,
     segments: LinkedList<Position>,
     status: Status,
 }
 
 impl Snake {
-    pub fn new(origin: Position) -> Self {
+    pub fn new(origin: Position, dir: Direction) -> Self {
         Self {
-            dir: Direction::Up,
+            dir,
+            turns: VecDeque::with_capacity(MAX_QUEUED_TURNS),
             segments: LinkedList::from([origin]),
             status: Status::Moved,
         }
     }
 
+    // A snake that is already on its way, head first, e.g. one received
+    // from a server.
+    pub fn from_segments(segments: &[Position], dir: Direction) -> Self {
+        Self {
+            dir,
+            turns: VecDeque::with_capacity(MAX_QUEUED_TURNS),
+            segments: segments.iter().copied().collect(),
+            status: Status::Moved,
+        }
+    }
+
     pub fn update(&mut self, grid: &mut Grid) -> &Status {
-        let curr_pos = self.segments.front().unwrap();
-        let new_pos = curr_pos.move_in_direction(&self.dir);
-        let width = grid.width();
-        let height = grid.height();
+        let new_pos = self.next_head(grid);
+        if let Some(dir) = self.turns.pop_front() {
+            self.dir = dir;
+        }
 
-        if let Ok(Position { mut x, mut y }) = new_pos {
-            if x >= width || y >= height || grid[y*width + x] == Cell::Snake {
+        let width = grid.width();
+        if let Some(Position { mut x, mut y }) = new_pos {
+            if matches!(grid[y*width + x], Cell::Snake | Cell::Wall) {
                 self.status = Status::Died;
                 return &self.status;
             }
@@ -65,33 +82,143 @@ impl Snake {
         &self.status
     }
 
+    // Where the head goes on the next update, or `None` if it would leave
+    // the board.
+    pub fn next_head(&self, grid: &Grid) -> Option<Position> {
+        let dir = self.turns.front().unwrap_or(&self.dir);
+        let head = self.head();
+        let width = grid.width();
+        let height = grid.height();
+        let new_pos = match grid.walls() {
+            Walls::Solid => head.move_in_direction(dir).ok(),
+            Walls::Open => Some(head.wrap_in_direction(dir, width, height)),
+        };
+        new_pos.filter(|pos| pos.x < width && pos.y < height)
+    }
+
+    // From the head to the tail
+    pub fn segments(&self) -> impl Iterator<Item = &Position> {
+        self.segments.iter()
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        self.segments.contains(&position)
+    }
+
+    // Whether the tail stays put on the next update, as the snake just ate
+    pub fn is_growing(&self) -> bool {
+        self.status == Status::Ate
+    }
+
+    pub fn dir(&self) -> Direction {
+        self.dir
+    }
+
+    pub fn head(&self) -> Position {
+        *self.segments.front().unwrap()
+    }
+
+    pub fn tail(&self) -> Position {
+        *self.segments.back().unwrap()
+    }
+
     pub fn set_dir(&mut self, new_dir: Direction) {
         if !are_opposite(&self.dir, &new_dir) {
             self.dir = new_dir;
         }
     }
+
+    // Queues a turn to be applied on a later update. Each turn is checked
+    // against the one queued before it, so quick sequences like Left, Down
+    // while heading up are kept instead of overwriting each other.
+    pub fn queue_dir(&mut self, new_dir: Direction) {
+        let last_dir = *self.turns.back().unwrap_or(&self.dir);
+        if self.turns.len() < MAX_QUEUED_TURNS
+            && new_dir != last_dir
+            && !are_opposite(&last_dir, &new_dir)
+        {
+            self.turns.push_back(new_dir);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::grid::{ DEFAULT_GRID_WIDTH, DEFAULT_GRID_HEIGHT };
     use pretty_assertions::assert_eq as pretty_assert_eq;
 
+    fn new_grid() -> Grid {
+        Grid::new(DEFAULT_GRID_WIDTH, DEFAULT_GRID_HEIGHT)
+    }
+
+    #[test]
+    fn test_head_and_tail_follow_the_snake() {
+        let mut grid = new_grid();
+        let width = grid.width();
+        grid[2*width + 2] = Cell::Apple;
+        let mut snake = Snake::new(Position { x: 2, y: 3 }, Direction::Up);
+
+        snake.update(&mut grid);
+        snake.update(&mut grid);
+
+        assert_eq!(snake.head(), Position { x: 2, y: 1 });
+        assert_eq!(snake.tail(), Position { x: 2, y: 2 });
+    }
+
     #[test]
     fn test_update_if_crossing_the_top_edge_then_dies() {
         let origin = Position { x: 2, y: 0 };
-        let mut snake = Snake::new(origin);
+        let mut snake = Snake::new(origin, Direction::Up);
+
+        assert_eq!(snake.update(&mut new_grid()), &Status::Died);
+    }
+
+    #[test]
+    fn test_update_if_crossing_the_top_edge_with_open_walls_then_wraps() {
+        let origin = Position { x: 2, y: 0 };
+        let mut grid = new_grid();
+        grid.set_walls(Walls::Open);
+        let width = grid.width();
+        let height = grid.height();
+        let mut snake = Snake::new(origin, Direction::Up);
+
+        assert_eq!(snake.update(&mut grid), &Status::Moved);
+        assert_eq!(grid[(height - 1)*width + 2], Cell::Snake);
+        assert_eq!(grid[2], Cell::Empty);
+    }
+
+    #[test]
+    fn test_update_if_crossing_the_left_edge_with_open_walls_then_wraps() {
+        let origin = Position { x: 0, y: 5 };
+        let mut grid = new_grid();
+        grid.set_walls(Walls::Open);
+        let width = grid.width();
+        let mut snake = Snake::new(origin, Direction::Up);
+        snake.set_dir(Direction::Left);
+
+        assert_eq!(snake.update(&mut grid), &Status::Moved);
+        assert_eq!(grid[5*width + width - 1], Cell::Snake);
+    }
+
+    #[test]
+    fn test_update_if_hit_a_wall_then_dies() {
+        let origin = Position { x: 2, y: 2 };
+        let mut grid = new_grid();
+        let width = grid.width();
+        grid[2*width + 3] = Cell::Wall;
+        let mut snake = Snake::new(origin, Direction::Right);
 
-        assert_eq!(snake.update(&mut Grid::new()), &Status::Died);
+        assert_eq!(snake.update(&mut grid), &Status::Died);
     }
 
     #[test]
     fn test_update_if_encountered_apple_then_returns_ate() {
         let origin = Position { x: 2, y: 2 };
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let width = grid.width();
-        grid[1*width + 2] = Cell::Apple;
-        let mut snake = Snake::new(origin);
+        grid[width + 2] = Cell::Apple;
+        let mut snake = Snake::new(origin, Direction::Up);
 
         assert_eq!(snake.update(&mut grid), &Status::Ate);
     }
@@ -99,16 +226,16 @@ mod tests {
     #[test]
     fn test_update_if_no_obstacles_then_moves() {
         let origin = Position { x: 2, y: 11 };
-        let mut snake = Snake::new(origin);
+        let mut snake = Snake::new(origin, Direction::Up);
 
-        assert_eq!(snake.update(&mut Grid::new()), &Status::Moved);
+        assert_eq!(snake.update(&mut new_grid()), &Status::Moved);
     }
 
     #[test]
     fn test_update_if_moves_constantly_in_one_dir_then_dies_eventually() {
         let origin = Position { x: 4, y: 4 };
-        let mut grid = Grid::new();
-        let mut snake = Snake::new(origin);
+        let mut grid = new_grid();
+        let mut snake = Snake::new(origin, Direction::Up);
 
         for _ in 0..4 {
             snake.update(&mut grid);
@@ -120,21 +247,75 @@ mod tests {
     #[test]
     fn test_set_dir() {
         let origin = Position { x: 2, y: 0 };
-        let mut grid = Grid::new();
-        let mut snake = Snake::new(origin);
+        let mut grid = new_grid();
+        let mut snake = Snake::new(origin, Direction::Up);
 
         snake.set_dir(Direction::Left);
 
         assert_eq!(snake.update(&mut grid), &Status::Moved);
     }
 
+    #[test]
+    fn test_queue_dir_applies_one_turn_per_update() {
+        let origin = Position { x: 5, y: 5 };
+        let mut grid = new_grid();
+        let width = grid.width();
+        let mut snake = Snake::new(origin, Direction::Up);
+
+        snake.queue_dir(Direction::Left);
+        snake.queue_dir(Direction::Down);
+        snake.update(&mut grid);
+
+        assert_eq!(grid[5*width + 4], Cell::Snake);
+
+        snake.update(&mut grid);
+
+        assert_eq!(grid[6*width + 4], Cell::Snake);
+    }
+
+    #[test]
+    fn test_queue_dir_checks_turns_against_the_last_queued_one() {
+        let origin = Position { x: 5, y: 5 };
+        let mut grid = new_grid();
+        let width = grid.width();
+        let mut snake = Snake::new(origin, Direction::Up);
+
+        snake.queue_dir(Direction::Up);    // Same direction, ignored
+        snake.queue_dir(Direction::Down);  // Opposite of Up, ignored
+        snake.queue_dir(Direction::Left);
+        snake.queue_dir(Direction::Right); // Opposite of Left, ignored
+        snake.update(&mut grid);
+        snake.update(&mut grid);
+
+        assert_eq!(grid[5*width + 3], Cell::Snake);
+    }
+
+    #[test]
+    fn test_queue_dir_caps_the_number_of_queued_turns() {
+        let origin = Position { x: 10, y: 10 };
+        let mut grid = new_grid();
+        let width = grid.width();
+        let mut snake = Snake::new(origin, Direction::Up);
+
+        snake.queue_dir(Direction::Left);
+        snake.queue_dir(Direction::Up);
+        snake.queue_dir(Direction::Right);
+        snake.queue_dir(Direction::Down); // Dropped, the queue is full
+        for _ in 0..4 {
+            snake.update(&mut grid);
+        }
+
+        // Left, up, right, right
+        assert_eq!(grid[9*width + 11], Cell::Snake);
+    }
+
     #[test]
     fn test_update_if_moved_then_the_grid_is_updated_correctly() {
         let origin = Position { x: 1, y: 2 };
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let width = grid.width();
         grid[6*width + 6] = Cell::Apple;
-        let mut snake = Snake::new(origin);
+        let mut snake = Snake::new(origin, Direction::Up);
         snake.update(&mut grid);
 
         let expected = "\
@@ -166,15 +347,15 @@ mod tests {
     #[test]
     fn test_update_if_ate_apples_then_the_grid_is_updated_correctly() {
         let origin = Position { x: 1, y: 3 };
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let width = grid.width();
-        grid[1*width + 1] = Cell::Apple;
-        let mut snake = Snake::new(origin);
+        grid[width + 1] = Cell::Apple;
+        let mut snake = Snake::new(origin, Direction::Up);
 
         snake.update(&mut grid); // (1, 2)
         snake.update(&mut grid); // (1, 1)
         let width = grid.width();
-        grid[1*width + 2] = Cell::Apple;
+        grid[width + 2] = Cell::Apple;
         snake.set_dir(Direction::Right);
         snake.update(&mut grid); // (2, 1)
         snake.set_dir(Direction::Down);
@@ -210,12 +391,12 @@ mod tests {
     fn test_update_if_collided_with_itself_then_dies() {
         let origin = Position { x: 1, y: 18 };
         let oy = origin.y;
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let width = grid.width();
         for y in (1..18).step_by(2) {
             grid[y*width + 1] = Cell::Apple;
         }
-        let mut snake = Snake::new(origin);
+        let mut snake = Snake::new(origin, Direction::Up);
 
         for _ in 1..oy {
             snake.update(&mut grid);
@@ -238,11 +419,11 @@ mod tests {
     #[test]
     fn test_update_when_snake_eats_second_time_in_a_row() {
         let origin = Position { x: 1, y: 4 };
-        let mut grid = Grid::new();
+        let mut grid = new_grid();
         let width = grid.width();
         grid[2*width + 1] = Cell::Apple;
         grid[3*width + 1] = Cell::Apple;
-        let mut snake = Snake::new(origin);
+        let mut snake = Snake::new(origin, Direction::Up);
 
         for _ in 0..3 {
             snake.update(&mut grid);
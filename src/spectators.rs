@@ -0,0 +1,346 @@
+use std::io::{ self, Write };
+use std::net::{ TcpListener, TcpStream };
+use termion::{ clear, cursor, style };
+
+use crate::frame::DiffRenderer;
+use crate::game::GameError;
+use crate::glyphs::Glyphs;
+use crate::grid::{ Grid, Layout };
+use crate::net::NetError;
+use crate::renderer::{ Renderer, Snapshot };
+use crate::theme::Theme;
+
+// Output a viewer has not taken yet beyond which it is dropped. It is well
+// above a full frame of the largest board, as frames are only queued once
+// the output before them was sent.
+const MAX_BACKLOG: usize = 1 << 20;
+
+// Read-only viewers of a game, connected with e.g. `nc` or `telnet`. They are
+// sent the same frames as the player's terminal, drawn from the top left
+// corner, and nothing they send is ever read.
+pub struct Spectators {
+    listener: TcpListener,
+    theme: &'static Theme,
+    glyphs: &'static Glyphs,
+    layout: Layout,
+    viewers: Vec<Viewer>,
+    // Size of the last frame, to center messages on it
+    size: (usize, usize),
+    dimmed: bool,
+}
+
+impl Spectators {
+    pub fn bind(
+        port: u16,
+        theme: &'static Theme,
+        glyphs: &'static Glyphs,
+        layout: Layout,
+    ) -> Result<Self, NetError> {
+        let listener = match TcpListener::bind(("0.0.0.0", port)) {
+            Ok(listener) => listener,
+            Err(_) => return Err(NetError::Bind),
+        };
+        // New viewers are picked up whenever a frame is drawn
+        if listener.set_nonblocking(true).is_err() {
+            return Err(NetError::Bind);
+        }
+
+        Ok(Self {
+            listener,
+            theme,
+            glyphs,
+            layout,
+            viewers: Vec::new(),
+            size: (0, 0),
+            dimmed: false,
+        })
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.listener.local_addr().ok().map(|addr| addr.port())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.viewers.is_empty()
+    }
+
+    fn accept(&mut self) {
+        // Writes never block, so that a slow viewer cannot hold up the game
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                let hello = format!("{}{}", clear::All, cursor::Hide);
+                self.viewers.push(Viewer {
+                    stream,
+                    diff: DiffRenderer::new(),
+                    pending: hello.into_bytes(),
+                });
+            }
+        }
+    }
+
+    // Sends each viewer what `f` writes for it. Frames are skipped for
+    // viewers that have not taken all of their earlier output yet, and the
+    // next one they get is drawn in full.
+    fn send(&mut self, frame: bool, mut f: impl FnMut(&mut DiffRenderer, &mut Vec<u8>)) {
+        self.viewers.retain_mut(|viewer| {
+            if viewer.flush().is_err() {
+                return false;
+            }
+            if frame && !viewer.pending.is_empty() {
+                viewer.diff.invalidate();
+                return true;
+            }
+            f(&mut viewer.diff, &mut viewer.pending);
+            viewer.pending.len() <= MAX_BACKLOG && viewer.flush().is_ok()
+        });
+    }
+
+    pub fn render(&mut self, snapshot: &Snapshot) {
+        self.accept();
+
+        let frame = snapshot.frame(self.layout, self.glyphs, self.theme);
+        let (width, height) = snapshot.grid.size_in_chars(self.layout);
+        self.size = (width, height + 1);
+
+        let invalidate = snapshot.dimmed != self.dimmed;
+        self.dimmed = snapshot.dimmed;
+        let prefix = if snapshot.dimmed {
+            style::Faint.to_string()
+        } else {
+            String::new()
+        };
+        self.send(true, |diff, out| {
+            if invalidate {
+                diff.invalidate();
+            }
+            out.extend_from_slice(prefix.as_bytes());
+            // Writing to memory does not fail
+            let _ = diff.draw(out, frame.clone(), (1, 1));
+            out.extend_from_slice(style::Reset.to_string().as_bytes());
+        });
+    }
+
+    pub fn render_message(&mut self, lines: &[String]) {
+        self.accept();
+
+        let (width, height) = self.size;
+        let top = (height/2).saturating_sub(1).max(1);
+        let mut text = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            let left = width.saturating_sub(line.chars().count())/2 + 1;
+            let goto = cursor::Goto(left as u16, (top + i) as u16);
+            text.push_str(&format!("{}{}", goto, line));
+        }
+        self.send(false, |diff, out| {
+            diff.invalidate();
+            out.extend_from_slice(text.as_bytes());
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.send(false, |diff, out| {
+            diff.invalidate();
+            out.extend_from_slice(clear::All.to_string().as_bytes());
+        });
+    }
+}
+
+// Leaves the viewers' terminals usable once the game is over
+impl Drop for Spectators {
+    fn drop(&mut self) {
+        let below = cursor::Goto(1, self.size.1 as u16 + 1);
+        let text = format!("{}{}{}\r\n", style::Reset, below, cursor::Show);
+        self.send(false, |_, out| out.extend_from_slice(text.as_bytes()));
+    }
+}
+
+struct Viewer {
+    stream: TcpStream,
+    diff: DiffRenderer,
+    // Output the socket did not take yet
+    pending: Vec<u8>,
+}
+
+impl Viewer {
+    // Writes as much of the pending output as the socket takes. Fails only
+    // when the viewer is gone.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Draws with `renderer` and shows every frame to spectators as well.
+pub struct SpectatedRenderer<R: Renderer> {
+    renderer: R,
+    spectators: Spectators,
+}
+
+impl<R: Renderer> SpectatedRenderer<R> {
+    pub fn new(renderer: R, spectators: Spectators) -> Self {
+        Self {
+            renderer,
+            spectators,
+        }
+    }
+}
+
+impl<R: Renderer> Renderer for SpectatedRenderer<R> {
+    fn board_size(&self, grid: &Grid) -> (usize, usize) {
+        self.renderer.board_size(grid)
+    }
+
+    fn size(&self) -> Result<(u16, u16), GameError> {
+        self.renderer.size()
+    }
+
+    fn open(&mut self) -> Result<(), GameError> {
+        self.renderer.open()
+    }
+
+    fn close(&mut self) -> Result<(), GameError> {
+        self.renderer.close()
+    }
+
+    fn clear(&mut self) -> Result<(), GameError> {
+        self.spectators.clear();
+        self.renderer.clear()
+    }
+
+    fn render(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        self.spectators.render(snapshot);
+        self.renderer.render(snapshot)
+    }
+
+    fn render_message(&mut self, lines: &[String]) -> Result<(), GameError> {
+        self.spectators.render_message(lines);
+        self.renderer.render_message(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::time::Duration;
+
+    use crate::engine::Engine;
+    use crate::grid::Walls;
+    use crate::level::Level;
+    use crate::renderer::MemoryRenderer;
+    use crate::difficulty::Speed;
+
+    fn spectated() -> SpectatedRenderer<MemoryRenderer> {
+        let spectators = Spectators::bind(
+            0,
+            Theme::mono(),
+            Glyphs::from_name("ascii").unwrap(),
+            Layout::Compact,
+        ).unwrap();
+        SpectatedRenderer::new(MemoryRenderer::new((80, 24)), spectators)
+    }
+
+    fn connect(renderer: &SpectatedRenderer<MemoryRenderer>) -> TcpStream {
+        let port = renderer.spectators.port().unwrap();
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        stream
+    }
+
+    fn receive(stream: &mut TcpStream, text: &str) -> String {
+        let mut received = Vec::new();
+        let mut buffer = [0; 1024];
+        while !String::from_utf8_lossy(&received).contains(text) {
+            let n = stream.read(&mut buffer).unwrap();
+            assert!(n > 0, "Connection closed before {:?}", text);
+            received.extend_from_slice(&buffer[..n]);
+        }
+        String::from_utf8(received).unwrap()
+    }
+
+    fn render(renderer: &mut SpectatedRenderer<MemoryRenderer>, engine: &Engine) {
+        let snapshot = Snapshot {
+            grid: engine.grid(),
+            snakes: engine.snakes(),
+            scores: engine.scores(),
+            speed: Speed::default(),
+            status: "",
+            dimmed: false,
+        };
+        renderer.render(&snapshot).unwrap();
+    }
+
+    #[test]
+    fn test_render_sends_frames_to_spectators() {
+        let engine = Engine::new(&Level::empty(4, 2), Walls::Solid, 0);
+        let mut renderer = spectated();
+        let mut stream = connect(&renderer);
+        // Viewers are accepted when a frame is drawn, so the connection has
+        // to be established first
+        while renderer.spectators.is_empty() {
+            render(&mut renderer, &engine);
+        }
+
+        let received = receive(&mut stream, "Score: 0");
+
+        assert!(received.starts_with(&format!("{}{}", clear::All, cursor::Hide)));
+        assert!(received.contains("+----+"));
+        assert!(!renderer.renderer.frames.is_empty());
+    }
+
+    #[test]
+    fn test_render_when_spectator_falls_behind_then_keeps_it() {
+        let engine = Engine::new(&Level::empty(200, 100), Walls::Solid, 0);
+        let mut renderer = spectated();
+        let mut stream = connect(&renderer);
+        while renderer.spectators.is_empty() {
+            render(&mut renderer, &engine);
+        }
+
+        // Full frames while the viewer reads nothing, until the socket
+        // buffers are full
+        while renderer.spectators.viewers[0].pending.is_empty() {
+            renderer.clear().unwrap();
+            render(&mut renderer, &engine);
+        }
+        render(&mut renderer, &engine);
+
+        assert!(!renderer.spectators.is_empty());
+        receive(&mut stream, "Score: 0");
+    }
+
+    #[test]
+    fn test_render_message_when_spectator_left_then_drops_it() {
+        let engine = Engine::new(&Level::empty(4, 2), Walls::Solid, 0);
+        let mut renderer = spectated();
+        let stream = connect(&renderer);
+        while renderer.spectators.is_empty() {
+            render(&mut renderer, &engine);
+        }
+        drop(stream);
+
+        // The first write after the other side closed may still succeed
+        for _ in 0..100 {
+            renderer.spectators.render_message(&[String::from(" You won! ")]);
+            if renderer.spectators.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        renderer.render_message(&[String::from(" You won! ")]).unwrap();
+
+        assert!(renderer.spectators.is_empty());
+        assert_eq!(renderer.renderer.messages.len(), 1);
+    }
+}
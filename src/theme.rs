@@ -0,0 +1,197 @@
+// Color escape sequences are written directly rather than through termion,
+// which leaves them out whenever NO_COLOR is set, even if the player then
+// picked a theme explicitly.
+
+pub const RESET_FG: &str = "\x1b[39m";
+pub const RESET_BG: &str = "\x1b[49m";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    // One of the 16 standard colors, understood by nearly every terminal
+    Ansi(u8),
+    // One of the 256 indexed colors
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    // Escape sequence that sets the foreground color.
+    pub fn fg(self) -> String {
+        match self {
+            Color::Ansi(n) if n < 8 => format!("\x1b[{}m", 30 + n),
+            Color::Ansi(n) => format!("\x1b[{}m", 90 + (n - 8)%8),
+            Color::Indexed(n) => format!("\x1b[38;5;{}m", n),
+            Color::Rgb(r, g, b) => format!("\x1b[38;2;{};{};{}m", r, g, b),
+        }
+    }
+
+    // Escape sequence that sets the background color.
+    pub fn bg(self) -> String {
+        match self {
+            Color::Ansi(n) if n < 8 => format!("\x1b[{}m", 40 + n),
+            Color::Ansi(n) => format!("\x1b[{}m", 100 + (n - 8)%8),
+            Color::Indexed(n) => format!("\x1b[48;5;{}m", n),
+            Color::Rgb(r, g, b) => format!("\x1b[48;2;{};{};{}m", r, g, b),
+        }
+    }
+}
+
+// What a character on the board belongs to. When a character is shared by
+// several parts, the greatest one decides its color.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Part {
+    Border,
+    Wall,
+    Body,
+    Tail,
+    // Body and tail of the second player's snake
+    Rival,
+    Apple,
+    Head,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    head: Option<Color>,
+    body: Option<Color>,
+    tail: Option<Color>,
+    rival: Option<Color>,
+    apple: Option<Color>,
+    walls: Option<Color>,
+    border: Option<Color>,
+}
+
+// The snake and the apple never differ by red and green alone, which many
+// players cannot tell apart.
+pub const THEMES: [Theme; 4] = [
+    Theme {
+        name: "classic",
+        head: Some(Color::Ansi(11)),
+        body: Some(Color::Ansi(14)),
+        tail: Some(Color::Ansi(6)),
+        rival: Some(Color::Ansi(13)),
+        apple: Some(Color::Ansi(9)),
+        walls: Some(Color::Ansi(8)),
+        border: Some(Color::Ansi(7)),
+    },
+    Theme {
+        name: "forest",
+        head: Some(Color::Indexed(226)),
+        body: Some(Color::Indexed(33)),
+        tail: Some(Color::Indexed(25)),
+        rival: Some(Color::Indexed(171)),
+        apple: Some(Color::Indexed(202)),
+        walls: Some(Color::Indexed(94)),
+        border: Some(Color::Indexed(65)),
+    },
+    Theme {
+        name: "neon",
+        head: Some(Color::Rgb(255, 235, 60)),
+        body: Some(Color::Rgb(0, 200, 255)),
+        tail: Some(Color::Rgb(0, 110, 170)),
+        rival: Some(Color::Rgb(255, 160, 40)),
+        apple: Some(Color::Rgb(255, 50, 140)),
+        walls: Some(Color::Rgb(150, 90, 255)),
+        border: Some(Color::Rgb(90, 90, 120)),
+    },
+    Theme {
+        name: "mono",
+        head: None,
+        body: None,
+        tail: None,
+        rival: None,
+        apple: None,
+        walls: None,
+        border: None,
+    },
+];
+
+impl Theme {
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        THEMES.iter().find(|theme| theme.name == name)
+    }
+
+    pub fn default_theme() -> &'static Self {
+        &THEMES[0]
+    }
+
+    // Used when the user asked for no color, see https://no-color.org
+    pub fn mono() -> &'static Self {
+        &THEMES[THEMES.len() - 1]
+    }
+
+    // Whether every part has a color of its own
+    pub fn has_colors(&self) -> bool {
+        [
+            self.head,
+            self.body,
+            self.tail,
+            self.rival,
+            self.apple,
+            self.walls,
+            self.border,
+        ].iter().all(Option::is_some)
+    }
+
+    pub fn color(&self, part: Part) -> Option<Color> {
+        match part {
+            Part::Border => self.border,
+            Part::Wall => self.walls,
+            Part::Body => self.body,
+            Part::Tail => self.tail,
+            Part::Rival => self.rival,
+            Part::Apple => self.apple,
+            Part::Head => self.head,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fg() {
+        assert_eq!(Color::Ansi(1).fg(), "\x1b[31m");
+        assert_eq!(Color::Ansi(9).fg(), "\x1b[91m");
+        assert_eq!(Color::Indexed(202).fg(), "\x1b[38;5;202m");
+        assert_eq!(Color::Rgb(1, 2, 3).fg(), "\x1b[38;2;1;2;3m");
+    }
+
+    #[test]
+    fn test_bg() {
+        assert_eq!(Color::Ansi(1).bg(), "\x1b[41m");
+        assert_eq!(Color::Ansi(9).bg(), "\x1b[101m");
+        assert_eq!(Color::Indexed(202).bg(), "\x1b[48;5;202m");
+        assert_eq!(Color::Rgb(1, 2, 3).bg(), "\x1b[48;2;1;2;3m");
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Theme::from_name("neon").map(|theme| theme.name), Some("neon"));
+        assert_eq!(Theme::from_name("rainbow"), None);
+    }
+
+    #[test]
+    fn test_themes_tell_every_part_apart() {
+        let parts = [
+            Part::Border,
+            Part::Wall,
+            Part::Body,
+            Part::Tail,
+            Part::Rival,
+            Part::Apple,
+            Part::Head,
+        ];
+
+        for theme in THEMES.iter().filter(|theme| theme.name != "mono") {
+            let mut colors = parts.iter()
+                .map(|&part| theme.color(part).map(|color| color.fg()))
+                .collect::<Vec<_>>();
+            colors.sort();
+            colors.dedup();
+            assert_eq!(colors.len(), parts.len(), "{}", theme.name);
+        }
+    }
+}